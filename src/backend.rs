@@ -0,0 +1,428 @@
+//! Where the screen is drawn and the keys come from.
+//!
+//! The editor only talks to a Backend. On a terminal that's RustBox, or a
+//! TrueColorTerminal when the theme has 24-bit colours; in tests it's a Grid
+//! (src/grid.rs), which keeps the screen in memory and plays back the keys it
+//! was given.
+//!
+//! Keys, events, colours and styles are the editor's own types, RustBox's are
+//! only used to talk to termbox.
+
+use std::cell::{Cell, RefCell};
+use std::io::{self, Write};
+use std::ops::BitOr;
+use std::rc::Rc;
+use std::time::Duration;
+
+use rustbox::{self, InitOptions, OutputMode, RustBox};
+
+use error::Error;
+use theme::{self, Face};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Key {
+    Tab,
+    Enter,
+    Esc,
+    Backspace,
+    Right,
+    Left,
+    Up,
+    Down,
+    Delete,
+    Insert,
+    Home,
+    End,
+    PageUp,
+    PageDown,
+    Char(char),
+    Ctrl(char),
+    F(u32),
+    Unknown(u16),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Event {
+    Key(Key),
+    // the screen is this many columns and rows now
+    Resize(usize, usize),
+    // none came in time, or it was one we don't use
+    None,
+}
+
+/// `Default` is whatever the terminal shows text in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Color {
+    Default,
+    Black,
+    Red,
+    Green,
+    Yellow,
+    Blue,
+    Magenta,
+    Cyan,
+    White,
+    // from the 256 colour palette
+    Byte(u8),
+    Rgb(u8, u8, u8),
+}
+
+/// Any of bold, underline and reverse, combined with `|`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Style {
+    pub bold: bool,
+    pub underline: bool,
+    pub reverse: bool,
+}
+
+impl Style {
+    pub const NORMAL: Style = Style {bold: false, underline: false, reverse: false};
+    pub const BOLD: Style = Style {bold: true, ..Style::NORMAL};
+    pub const UNDERLINE: Style = Style {underline: true, ..Style::NORMAL};
+    pub const REVERSE: Style = Style {reverse: true, ..Style::NORMAL};
+}
+
+impl BitOr for Style {
+    type Output = Style;
+
+    fn bitor(self, other: Style) -> Style {
+        Style {
+            bold: self.bold || other.bold,
+            underline: self.underline || other.underline,
+            reverse: self.reverse || other.reverse,
+        }
+    }
+}
+
+pub trait Backend {
+    fn width(&self) -> usize;
+    fn height(&self) -> usize;
+    // Drawing goes to a back buffer, it shows up on screen on present().
+    fn print_char(&self, x: usize, y: usize, face: Face, character: char);
+    // None hides the cursor
+    fn set_cursor(&self, position: Option<(usize, usize)>);
+    fn clear(&self);
+    fn present(&self);
+    // Waits for the next event, or until `timeout` passes if there's one,
+    // and then it's Event::NoEvent.
+    fn poll_event(&self, timeout: Option<Duration>) -> Event;
+
+    fn print(&self, x: usize, y: usize, face: Face, text: &str) {
+        for (i, character) in text.chars().enumerate() {
+            self.print_char(x + i, y, face, character);
+        }
+    }
+}
+
+/// Takes over the terminal. Extended colours need termbox's 256 colour mode,
+/// 24-bit ones a TrueColorTerminal.
+pub fn terminal(extended_colours: bool, true_colours: bool) -> Result<Box<dyn Backend>, Error> {
+    let output_mode = if extended_colours { OutputMode::EightBit } else { OutputMode::Current };
+    let rustbox = RustBox::init(InitOptions {output_mode, ..Default::default()})?;
+    if true_colours {
+        Ok(Box::new(TrueColorTerminal::new(rustbox)))
+    } else {
+        Ok(Box::new(rustbox))
+    }
+}
+
+impl Backend for RustBox {
+    fn width(&self) -> usize {
+        RustBox::width(self)
+    }
+
+    fn height(&self) -> usize {
+        RustBox::height(self)
+    }
+
+    fn print_char(&self, x: usize, y: usize, face: Face, character: char) {
+        RustBox::print_char(self, x, y, rustbox_style(face.style), rustbox_color(face.fg), rustbox_color(face.bg),
+                            character);
+    }
+
+    fn set_cursor(&self, position: Option<(usize, usize)>) {
+        match position {
+            Some((x, y)) => RustBox::set_cursor(self, x as isize, y as isize),
+            None => RustBox::set_cursor(self, -1, -1),
+        }
+    }
+
+    fn clear(&self) {
+        RustBox::clear(self);
+    }
+
+    fn present(&self) {
+        RustBox::present(self);
+    }
+
+    fn poll_event(&self, timeout: Option<Duration>) -> Event {
+        let event = match timeout {
+            Some(timeout) => self.peek_event(timeout, false),
+            None => RustBox::poll_event(self, false),
+        };
+        match event {
+            Ok(rustbox::Event::KeyEvent(key)) => Event::Key(key_from_rustbox(key)),
+            Ok(rustbox::Event::ResizeEvent(width, height)) => Event::Resize(width as usize, height as usize),
+            _ => Event::None,
+        }
+    }
+}
+
+fn key_from_rustbox(key: rustbox::Key) -> Key {
+    match key {
+        rustbox::Key::Tab             => Key::Tab,
+        rustbox::Key::Enter           => Key::Enter,
+        rustbox::Key::Esc             => Key::Esc,
+        rustbox::Key::Backspace       => Key::Backspace,
+        rustbox::Key::Right           => Key::Right,
+        rustbox::Key::Left            => Key::Left,
+        rustbox::Key::Up              => Key::Up,
+        rustbox::Key::Down            => Key::Down,
+        rustbox::Key::Delete          => Key::Delete,
+        rustbox::Key::Insert          => Key::Insert,
+        rustbox::Key::Home            => Key::Home,
+        rustbox::Key::End             => Key::End,
+        rustbox::Key::PageUp          => Key::PageUp,
+        rustbox::Key::PageDown        => Key::PageDown,
+        rustbox::Key::Char(character) => Key::Char(character),
+        rustbox::Key::Ctrl(character) => Key::Ctrl(character),
+        rustbox::Key::F(number)       => Key::F(number),
+        rustbox::Key::Unknown(code)   => Key::Unknown(code),
+    }
+}
+
+fn rustbox_color(color: Color) -> rustbox::Color {
+    match color {
+        Color::Default     => rustbox::Color::Default,
+        Color::Black       => rustbox::Color::Black,
+        Color::Red         => rustbox::Color::Red,
+        Color::Green       => rustbox::Color::Green,
+        Color::Yellow      => rustbox::Color::Yellow,
+        Color::Blue        => rustbox::Color::Blue,
+        Color::Magenta     => rustbox::Color::Magenta,
+        Color::Cyan        => rustbox::Color::Cyan,
+        Color::White       => rustbox::Color::White,
+        Color::Byte(index) => rustbox::Color::Byte(index as u16),
+        Color::Rgb(red, green, blue) => rustbox::Color::Byte(theme::closest_in_palette(red, green, blue) as u16),
+    }
+}
+
+fn rustbox_style(style: Style) -> rustbox::Style {
+    let mut rustbox_style = rustbox::RB_NORMAL;
+    if style.bold {
+        rustbox_style = rustbox_style | rustbox::RB_BOLD;
+    }
+    if style.underline {
+        rustbox_style = rustbox_style | rustbox::RB_UNDERLINE;
+    }
+    if style.reverse {
+        rustbox_style = rustbox_style | rustbox::RB_REVERSE;
+    }
+    rustbox_style
+}
+
+/// Termbox can't send 24-bit colours, so on terminals that take them the
+/// screen is drawn here instead. RustBox still reads the keys and keeps the
+/// terminal in the right mode.
+pub struct TrueColorTerminal {
+    rustbox: RustBox,
+    width: Cell<usize>,
+    height: Cell<usize>,
+    // what's being drawn, and what's on screen as far as we know
+    drawing: RefCell<Vec<(char, Face)>>,
+    shown: RefCell<Vec<Option<(char, Face)>>>,
+    cursor: Cell<Option<(usize, usize)>>,
+}
+
+const BLANK: Face = Face {style: Style::NORMAL, fg: Color::Default, bg: Color::Default};
+
+impl TrueColorTerminal {
+    pub fn new(rustbox: RustBox) -> TrueColorTerminal {
+        let (width, height) = (rustbox.width(), rustbox.height());
+        TrueColorTerminal {
+            rustbox,
+            width: Cell::new(width),
+            height: Cell::new(height),
+            drawing: RefCell::new(vec![(' ', BLANK); width * height]),
+            shown: RefCell::new(vec![None; width * height]),
+            cursor: Cell::new(None),
+        }
+    }
+}
+
+impl Backend for TrueColorTerminal {
+    fn width(&self) -> usize {
+        self.width.get()
+    }
+
+    fn height(&self) -> usize {
+        self.height.get()
+    }
+
+    fn print_char(&self, x: usize, y: usize, face: Face, character: char) {
+        if x < self.width.get() && y < self.height.get() {
+            self.drawing.borrow_mut()[y * self.width.get() + x] = (character, face);
+        }
+    }
+
+    fn set_cursor(&self, position: Option<(usize, usize)>) {
+        self.cursor.set(position);
+    }
+
+    fn clear(&self) {
+        for cell in self.drawing.borrow_mut().iter_mut() {
+            *cell = (' ', BLANK);
+        }
+    }
+
+    fn present(&self) {
+        let mut output = screen_changes(&self.drawing.borrow(), &mut self.shown.borrow_mut(), self.width.get());
+        match self.cursor.get() {
+            Some((x, y)) => output.push_str(&format!("\x1b[{};{}H\x1b[?25h", y + 1, x + 1)),
+            None => output.push_str("\x1b[?25l"),
+        }
+        // there's nowhere to show it went wrong
+        let mut stdout = io::stdout();
+        let _ = stdout.write_all(output.as_bytes()).and_then(|_| stdout.flush());
+    }
+
+    fn poll_event(&self, timeout: Option<Duration>) -> Event {
+        let event = Backend::poll_event(&self.rustbox, timeout);
+        // the screen starts over, blank
+        if let Event::Resize(width, height) = event {
+            self.width.set(width);
+            self.height.set(height);
+            *self.drawing.borrow_mut() = vec![(' ', BLANK); width * height];
+            *self.shown.borrow_mut() = vec![None; width * height];
+        }
+        event
+    }
+}
+
+// What to send the terminal so it shows `drawing` instead of `shown`, which
+// is then up to date.
+fn screen_changes(drawing: &[(char, Face)], shown: &mut [Option<(char, Face)>], width: usize) -> String {
+    let mut output = String::new();
+    // the terminal's cursor and the face it draws with, once we know them
+    let mut position = None;
+    let mut face = None;
+    for (i, &(character, cell_face)) in drawing.iter().enumerate() {
+        if shown[i] == Some((character, cell_face)) {
+            continue;
+        }
+        let (x, y) = (i % width, i / width);
+        if position != Some((x, y)) {
+            output.push_str(&format!("\x1b[{};{}H", y + 1, x + 1));
+        }
+        if face != Some(cell_face) {
+            output.push_str(&sgr(cell_face));
+            face = Some(cell_face);
+        }
+        output.push(character);
+        position = Some((x + 1, y));
+        shown[i] = Some((character, cell_face));
+    }
+    if face.is_some() {
+        output.push_str("\x1b[0m");
+    }
+    output
+}
+
+// the escape sequence that draws with `face` from then on
+fn sgr(face: Face) -> String {
+    let mut codes = vec!["0".to_string()];
+    if face.style.bold {
+        codes.push("1".to_string());
+    }
+    if face.style.underline {
+        codes.push("4".to_string());
+    }
+    if face.style.reverse {
+        codes.push("7".to_string());
+    }
+    codes.push(sgr_color(face.fg, 30));
+    codes.push(sgr_color(face.bg, 40));
+    format!("\x1b[{}m", codes.join(";"))
+}
+
+// `base` is 30 for the foreground and 40 for the background
+fn sgr_color(color: Color, base: u8) -> String {
+    let basic = |index: u8| (base + index).to_string();
+    match color {
+        Color::Default => basic(9),
+        Color::Black => basic(0),
+        Color::Red => basic(1),
+        Color::Green => basic(2),
+        Color::Yellow => basic(3),
+        Color::Blue => basic(4),
+        Color::Magenta => basic(5),
+        Color::Cyan => basic(6),
+        Color::White => basic(7),
+        Color::Byte(index) => format!("{};5;{}", base + 8, index),
+        Color::Rgb(red, green, blue) => format!("{};2;{};{};{}", base + 8, red, green, blue),
+    }
+}
+
+// so a test can keep hold of the Grid it gives the editor
+impl<B: Backend + ?Sized> Backend for Rc<B> {
+    fn width(&self) -> usize {
+        (**self).width()
+    }
+
+    fn height(&self) -> usize {
+        (**self).height()
+    }
+
+    fn print_char(&self, x: usize, y: usize, face: Face, character: char) {
+        (**self).print_char(x, y, face, character);
+    }
+
+    fn set_cursor(&self, position: Option<(usize, usize)>) {
+        (**self).set_cursor(position);
+    }
+
+    fn clear(&self) {
+        (**self).clear();
+    }
+
+    fn present(&self) {
+        (**self).present();
+    }
+
+    fn poll_event(&self, timeout: Option<Duration>) -> Event {
+        (**self).poll_event(timeout)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_sgr() {
+        assert_eq!(sgr(BLANK), "\x1b[0;39;49m");
+        let face = Face {style: Style::BOLD | Style::REVERSE, fg: Color::Rgb(251, 73, 52), bg: Color::Byte(235)};
+        assert_eq!(sgr(face), "\x1b[0;1;7;38;2;251;73;52;48;5;235m");
+        assert_eq!(sgr(Face {style: Style::UNDERLINE, fg: Color::Red, bg: Color::White}), "\x1b[0;4;31;47m");
+    }
+
+    #[test]
+    fn test_screen_changes() {
+        let red = Face {fg: Color::Rgb(255, 0, 0), ..BLANK};
+        let mut drawing = vec![(' ', BLANK); 6];
+        let mut shown = vec![None; 6];
+        drawing[1] = ('a', red);
+        drawing[2] = ('b', red);
+        drawing[4] = ('c', BLANK);
+        // everything the first time, jumping to the start of each row
+        assert_eq!(screen_changes(&drawing, &mut shown, 3),
+                   "\x1b[1;1H\x1b[0;39;49m \x1b[0;38;2;255;0;0;49mab\x1b[2;1H\x1b[0;39;49m c \x1b[0m");
+
+        // then only what changed
+        drawing[4] = ('d', red);
+        assert_eq!(screen_changes(&drawing, &mut shown, 3), "\x1b[2;2H\x1b[0;38;2;255;0;0;49md\x1b[0m");
+        assert_eq!(screen_changes(&drawing, &mut shown, 3), "");
+    }
+}
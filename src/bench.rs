@@ -0,0 +1,118 @@
+// Benchmarks for the rope backed Buffer against the Vec<Vec<char>> layout it
+// replaced. They need the `test` crate, so run them on nightly:
+//
+//     cargo +nightly bench --features nightly
+
+extern crate test;
+
+use self::test::Bencher;
+
+use super::{Buffer, Cursor};
+
+const LINES: usize = 100_000;
+
+fn big_file() -> String {
+    (0..LINES).map(|i| format!("{:06} let x = some_function(argument, 42);\n", i)).collect()
+}
+
+// The old layout, trimmed down to what the benchmarks exercise.
+struct VecBuffer {
+    data: Vec<Vec<char>>,
+}
+
+impl VecBuffer {
+    fn from_string(string: &str) -> VecBuffer {
+        VecBuffer {data: string.lines().map(|line| line.chars().collect()).collect()}
+    }
+
+    fn write_char(&mut self, cursor: &Cursor, character: char) {
+        self.data[cursor.y].insert(cursor.x, character);
+    }
+
+    fn newline(&mut self, cursor: &Cursor) {
+        let rest = self.data[cursor.y].split_off(cursor.x);
+        self.data.insert(cursor.y + 1, rest);
+    }
+
+    fn backspace(&mut self, cursor: &Cursor) {
+        if cursor.x > 0 {
+            self.data[cursor.y].remove(cursor.x - 1);
+        } else {
+            let line = self.data.remove(cursor.y);
+            self.data[cursor.y - 1].extend(line);
+        }
+    }
+
+    fn get_line(&self, line_number: usize) -> String {
+        self.data[line_number].iter().cloned().collect()
+    }
+}
+
+#[bench]
+fn bench_load_rope(b: &mut Bencher) {
+    let text = big_file();
+    b.iter(|| Buffer::from_string(&text));
+}
+
+#[bench]
+fn bench_load_vec(b: &mut Bencher) {
+    let text = big_file();
+    b.iter(|| VecBuffer::from_string(&text));
+}
+
+#[bench]
+fn bench_type_mid_file_rope(b: &mut Bencher) {
+    let mut buffer = Buffer::from_string(&big_file());
+    b.iter(|| {
+        buffer.write_char(&Cursor::new(10, LINES / 2), 'x');
+        buffer.backspace(&Cursor::new(11, LINES / 2));
+    });
+}
+
+#[bench]
+fn bench_type_mid_file_vec(b: &mut Bencher) {
+    let mut buffer = VecBuffer::from_string(&big_file());
+    b.iter(|| {
+        buffer.write_char(&Cursor::new(10, LINES / 2), 'x');
+        buffer.backspace(&Cursor::new(11, LINES / 2));
+    });
+}
+
+#[bench]
+fn bench_split_and_join_line_rope(b: &mut Bencher) {
+    let mut buffer = Buffer::from_string(&big_file());
+    b.iter(|| {
+        buffer.newline(&Cursor::new(10, LINES / 2));
+        buffer.backspace(&Cursor::new(0, LINES / 2 + 1));
+    });
+}
+
+#[bench]
+fn bench_split_and_join_line_vec(b: &mut Bencher) {
+    let mut buffer = VecBuffer::from_string(&big_file());
+    b.iter(|| {
+        buffer.newline(&Cursor::new(10, LINES / 2));
+        buffer.backspace(&Cursor::new(0, LINES / 2 + 1));
+    });
+}
+
+// roughly what a full screen render asks for
+#[bench]
+fn bench_get_screen_rope(b: &mut Bencher) {
+    let buffer = Buffer::from_string(&big_file());
+    b.iter(|| {
+        for line_number in LINES / 2..LINES / 2 + 50 {
+            test::black_box(buffer.get_line(line_number));
+        }
+    });
+}
+
+#[bench]
+fn bench_get_screen_vec(b: &mut Bencher) {
+    let buffer = VecBuffer::from_string(&big_file());
+    b.iter(|| {
+        for line_number in LINES / 2..LINES / 2 + 50 {
+            test::black_box(buffer.get_line(line_number));
+        }
+    });
+}
@@ -0,0 +1,815 @@
+//! The text being edited.
+//!
+//! A Buffer holds the text in a rope and changes it a char, a line break or
+//! a region at a time, the way keys do. Every change is recorded so it can be
+//! undone, and the highlighting state of the lines below it is kept up to
+//! date. Files are read into it with `from_string` and written back with
+//! `write_to`, keeping their line endings.
+
+use std::io::{self, Write};
+use std::iter;
+use std::rc::Rc;
+
+use cursor::{Cursor, Region};
+use highlight::{LineStates, State};
+use history::{ChangeKind, Edit, History};
+use language::Language;
+use rope::Rope;
+
+/// What an edit changed, so only that needs to be drawn again.
+#[derive(Clone, Debug, PartialEq)]
+pub enum BufferChanges {
+    /// a single char, at a column and a line
+    Char((usize, usize)),
+    /// whole lines, by number
+    Lines(Vec<usize>),
+    /// everything on screen
+    Buffer,
+    None
+}
+
+/// How lines end in the file. The buffer always uses '\n', this is only
+/// looked at when reading and writing.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LineEnding {
+    Lf,
+    Crlf,
+}
+
+impl LineEnding {
+    /// Whatever the first line ends with.
+    pub fn detect(text: &str) -> LineEnding {
+        match text.find('\n') {
+            Some(end) if text[..end].ends_with('\r') => LineEnding::Crlf,
+            _ => LineEnding::Lf,
+        }
+    }
+
+    /// As shown in the status bar.
+    pub fn name(self) -> &'static str {
+        match self {
+            LineEnding::Lf   => "LF",
+            LineEnding::Crlf => "CRLF",
+        }
+    }
+}
+
+/// The text being edited, with its undo history and what it's highlighted
+/// as.
+/// Text lives in a rope with every line terminated by a '\n', so a buffer
+/// without any newline has no lines at all. All changes to the rope go through
+/// insert_text and remove_text so they end up in the history.
+pub struct Buffer {
+    pub(crate) text: Rope,
+    pub(crate) history: History,
+    // what the text is highlighted as
+    pub(crate) language: Rc<Language>,
+    // the highlighting state each line starts in
+    line_states: LineStates,
+    // changed since it was loaded or saved
+    pub(crate) modified: bool,
+    // goes up with every change
+    pub(crate) revision: usize,
+    // what lines end with when saved
+    pub(crate) line_ending: LineEnding,
+    // Each line's own ending, when the file mixes them, so saving doesn't
+    // change the lines nobody touched. New lines get line_ending.
+    line_endings: Option<Vec<LineEnding>>,
+    // whether the last line ends with one, files without it are saved
+    // without it
+    final_newline: bool,
+}
+
+impl Buffer {
+    /// An empty buffer, that saves with LF line endings.
+    pub fn new() -> Buffer {
+        Buffer {
+            text: Rope::new(),
+            history: History::new(),
+            language: Rc::new(Language::plain()),
+            line_states: LineStates::new(),
+            modified: false,
+            revision: 0,
+            line_ending: LineEnding::Lf,
+            line_endings: None,
+            final_newline: true,
+        }
+    }
+
+    /// Mixed line endings are kept, lines added later end like the first
+    /// one.
+    pub fn from_string(string: &str) -> Buffer {
+        let mut text = String::with_capacity(string.len() + 1);
+        for line in string.lines() {
+            text.push_str(line);
+            text.push('\n');
+        }
+        let line_ending = LineEnding::detect(string);
+        let mut line_endings: Vec<LineEnding> = string.split_inclusive('\n')
+            .map(|line| if line.ends_with("\r\n") { LineEnding::Crlf } else { LineEnding::Lf })
+            .collect();
+        // the last line may not have one
+        if !string.ends_with('\n') {
+            line_endings.pop();
+            if !string.is_empty() {
+                line_endings.push(line_ending);
+            }
+        }
+        let mixed = line_endings.iter().any(|&ending| ending != line_ending);
+        Buffer {
+            text: Rope::from_string(&text),
+            history: History::new(),
+            language: Rc::new(Language::plain()),
+            line_states: LineStates::new(),
+            modified: false,
+            revision: 0,
+            line_ending,
+            line_endings: if mixed { Some(line_endings) } else { None },
+            // an empty file has no say, it gets one like a new one
+            final_newline: string.is_empty() || string.ends_with('\n'),
+        }
+    }
+
+    /// highlights the text as `language` from now on
+    pub fn set_language(&mut self, language: Rc<Language>) {
+        self.language = language;
+        self.line_states = LineStates::new();
+    }
+
+    pub fn language(&self) -> &Rc<Language> {
+        &self.language
+    }
+
+    pub fn line_ending(&self) -> LineEnding {
+        self.line_ending
+    }
+
+    /// Whether it changed since it was loaded or saved.
+    pub fn is_modified(&self) -> bool {
+        self.modified
+    }
+
+    /// A number that goes up with every change, to tell whether the text
+    /// changed since some earlier time.
+    pub fn revision(&self) -> usize {
+        self.revision
+    }
+
+    /// Whether lines in the file end in different ways.
+    pub fn has_mixed_line_endings(&self) -> bool {
+        self.line_endings.is_some()
+    }
+
+    /// Switches the line endings the file is saved with, every line's if
+    /// they were mixed. Returns whether they changed.
+    pub fn set_line_ending(&mut self, line_ending: LineEnding) -> bool {
+        if line_ending == self.line_ending && self.line_endings.is_none() {
+            return false;
+        }
+        self.line_ending = line_ending;
+        self.line_endings = None;
+        // undoing edits won't switch them back
+        self.mark_modified();
+        true
+    }
+
+    // for changes undo can't take back
+    pub(crate) fn mark_modified(&mut self) {
+        self.modified = true;
+        self.revision += 1;
+        self.history.forget_saved();
+    }
+
+    /// the text was just written to the file
+    pub fn mark_saved(&mut self) {
+        self.modified = false;
+        self.history.mark_saved();
+    }
+
+    /// The text as it goes in the file, with the buffer's line endings and
+    /// without the last newline if the file didn't have one.
+    pub fn write_to<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        if let Some(ref line_endings) = self.line_endings {
+            for (line_number, &ending) in line_endings.iter().enumerate() {
+                out.write_all(rope_line(&self.text, line_number).as_bytes())?;
+                if line_number + 1 < line_endings.len() || self.final_newline {
+                    out.write_all(if ending == LineEnding::Crlf { b"\r\n" } else { b"\n" })?;
+                }
+            }
+            return Ok(());
+        }
+        let chunks: Vec<&str> = self.text.chunks().collect();
+        for (i, &chunk) in chunks.iter().enumerate() {
+            let chunk = if i + 1 == chunks.len() && !self.final_newline {
+                chunk.strip_suffix('\n').unwrap_or(chunk)
+            } else {
+                chunk
+            };
+            match self.line_ending {
+                LineEnding::Lf   => out.write_all(chunk.as_bytes())?,
+                LineEnding::Crlf => out.write_all(chunk.replace('\n', "\r\n").as_bytes())?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Types `character` at the cursor.
+    pub fn write_char(&mut self, cursor: &Cursor, character: char) -> BufferChanges {
+        let &Cursor{x, y} = cursor;
+        let at = self.make_room(cursor);
+        // typing may start or end a comment or a string, changing how the
+        // lines after this one look
+        let last = self.insert_text(at, &character.to_string());
+        self.history.commit(ChangeKind::Typing, *cursor, Cursor::new(x + 1, y));
+        BufferChanges::Lines((y..last + 1).collect())
+    }
+
+    /// Insert text that may span several lines, the cursor ends up right
+    /// after it.
+    pub fn insert_string(&mut self, cursor: &Cursor, string: &str) -> (BufferChanges, Cursor) {
+        if string.is_empty() {
+            return (BufferChanges::None, *cursor);
+        }
+        let &Cursor{x, y} = cursor;
+        let at = self.make_room(cursor);
+        self.insert_text(at, string);
+
+        let newlines = string.matches('\n').count();
+        let last_line = string.rsplit('\n').next().unwrap_or("");
+        let (changes, new_cursor) = if newlines == 0 {
+            (BufferChanges::Lines(vec![y]), Cursor::new(x + last_line.chars().count(), y))
+        } else {
+            (BufferChanges::Buffer, Cursor::new(last_line.chars().count(), y + newlines))
+        };
+        self.history.commit(ChangeKind::Other, *cursor, new_cursor);
+        (changes, new_cursor)
+    }
+
+    // Make sure the cursor points at an existing line and isn't past its
+    // end, padding it with spaces if needed. Returns the cursor's char offset.
+    fn make_room(&mut self, cursor: &Cursor) -> usize {
+        let &Cursor{x, y} = cursor;
+        self.fill_lines(y);
+
+        let line_start = self.text.line_to_char(y);
+        let line_length = self.get_line_length(y);
+        if x > line_length {
+            let padding: String = (line_length..x).map(|_| ' ').collect();
+            self.insert_text(line_start + line_length, &padding);
+        }
+        line_start + x
+    }
+
+    /// Breaks the line at the cursor.
+    pub fn newline(&mut self, cursor: &Cursor) -> BufferChanges {
+        let &Cursor{x, y} = cursor;
+        // make sure we have enough lines
+        self.fill_lines(y);
+
+        // past the end of the line we just get an empty line below
+        let offset = x.min(self.get_line_length(y));
+        let line_start = self.text.line_to_char(y);
+        self.insert_text(line_start + offset, "\n");
+        self.history.commit(ChangeKind::Other, *cursor, Cursor::new(0, y + 1));
+        // we could optimize here if we have little following lines
+        BufferChanges::Buffer
+    }
+
+    /// Chars in a line, not counting its newline. Lines past the end are empty.
+    pub fn get_line_length(&self, line_number: usize) -> usize {
+        if line_number < self.count_lines() {
+            self.text.line_to_char(line_number + 1) - self.text.line_to_char(line_number) - 1
+        } else {
+            0
+        }
+    }
+
+    /// Deletes the char before the cursor, joining the line with the one above
+    /// at its start.
+    pub fn backspace(&mut self, cursor: &Cursor) -> BufferChanges {
+        let &Cursor{x, y} = cursor;
+        if y >= self.count_lines() {
+            return BufferChanges::None;
+        }
+
+        let line_start = self.text.line_to_char(y);
+        if x > 0 && x <= self.get_line_length(y) {
+            self.remove_text(line_start + x - 1, line_start + x);
+            self.history.commit(ChangeKind::Other, *cursor, Cursor::new(x - 1, y));
+            BufferChanges::Buffer
+        } else if x == 0 && y > 0 {
+            // if we want to delete back from the first position of a line,
+            // drop the previous newline so both lines become one.
+            let previous_line_length = self.get_line_length(y - 1);
+            self.remove_text(line_start - 1, line_start);
+            self.history.commit(ChangeKind::Other, *cursor,
+                                Cursor::new(previous_line_length, y - 1));
+            BufferChanges::Buffer
+        } else {
+            BufferChanges::None
+        }
+    }
+
+    /// Lines in the text, the last one ends with a newline.
+    pub fn count_lines(&self) -> usize {
+        self.text.count_newlines()
+    }
+
+    /// A line without its newline. Lines past the end are empty.
+    pub fn get_line(&self, line_number: usize) -> String {
+        rope_line(&self.text, line_number)
+    }
+
+    // the highlighting state a line starts in, see highlight_to
+    pub(crate) fn line_state(&self, line_number: usize) -> State {
+        self.line_states.get(line_number)
+    }
+
+    /// Makes sure we know the highlighting state of lines down to this one.
+    pub fn highlight_to(&mut self, line_number: usize) {
+        let text = &self.text;
+        self.line_states.lex_to(&self.language, line_number, text.count_newlines(), |line| rope_line(text, line));
+    }
+
+    fn fill_lines(&mut self, line_number: usize) {
+        let missing = (line_number + 1).saturating_sub(self.count_lines());
+        if missing > 0 {
+            let newlines: String = (0..missing).map(|_| '\n').collect();
+            let end = self.text.len_chars();
+            self.insert_text(end, &newlines);
+        }
+    }
+
+    /// char offset of a position, clamped to the text
+    pub fn cursor_to_char(&self, cursor: &Cursor) -> usize {
+        if cursor.y >= self.count_lines() {
+            self.text.len_chars()
+        } else {
+            self.text.line_to_char(cursor.y) + cursor.x.min(self.get_line_length(cursor.y))
+        }
+    }
+
+    /// The position of a char offset.
+    pub fn char_to_cursor(&self, offset: usize) -> Cursor {
+        let y = self.text.char_to_line(offset);
+        Cursor::new(offset - self.text.line_to_char(y), y)
+    }
+
+    /// The text in a region.
+    pub fn copy_region(&self, region: &Region) -> String {
+        self.text.slice(self.cursor_to_char(&region.start), self.cursor_to_char(&region.end))
+    }
+
+    /// Removes the text in a region and returns it.
+    pub fn cut_region(&mut self, region: &Region) -> (BufferChanges, String) {
+        let text = self.copy_region(region);
+        if text.is_empty() {
+            return (BufferChanges::None, text);
+        }
+        let start = self.cursor_to_char(&region.start);
+        self.remove_text(start, start + text.chars().count());
+        self.history.commit(ChangeKind::Other, region.end, region.start);
+        (BufferChanges::Buffer, text)
+    }
+
+    /// Removes the text in a region.
+    pub fn delete_region(&mut self, region: &Region) -> BufferChanges {
+        self.cut_region(region).0
+    }
+
+    /// Puts a string in place of the text in a region, as a single undo
+    /// step. Returns where the string ends.
+    pub fn replace_region(&mut self, region: &Region, string: &str) -> (BufferChanges, Cursor) {
+        let start = self.cursor_to_char(&region.start);
+        let end = self.cursor_to_char(&region.end);
+        if start == end && string.is_empty() {
+            return (BufferChanges::None, region.start);
+        }
+        self.replace_text(start, end, string);
+        let new_cursor = self.char_to_cursor(start + string.chars().count());
+        self.history.commit(ChangeKind::Other, region.end, new_cursor);
+        (BufferChanges::Buffer, new_cursor)
+    }
+
+    // Both return the last line whose highlighting may have changed.
+    fn insert_text(&mut self, at: usize, text: &str) -> usize {
+        let edit = Edit::Insert {at, text: text.to_string()};
+        let last = self.apply_edit(&edit);
+        self.history.record(edit);
+        last
+    }
+
+    fn remove_text(&mut self, start: usize, end: usize) -> usize {
+        let edit = Edit::Remove {at: start, text: self.text.slice(start, end)};
+        let last = self.apply_edit(&edit);
+        self.history.record(edit);
+        last
+    }
+
+    // changes the rope, keeping the highlighting states up to date
+    fn apply_edit(&mut self, edit: &Edit) -> usize {
+        let (line_number, removed, inserted) = match *edit {
+            Edit::Insert {at, ref text} => {
+                let line_number = self.text.char_to_line(at);
+                self.text.insert(at, text);
+                let inserted = text.matches('\n').count();
+                // the line's own ending goes with the last piece of it
+                let line_ending = self.line_ending;
+                if let Some(ref mut line_endings) = self.line_endings {
+                    line_endings.splice(line_number..line_number, iter::repeat_n(line_ending, inserted));
+                }
+                (line_number, 0, inserted)
+            },
+            Edit::Remove {at, ref text} => {
+                let line_number = self.text.char_to_line(at);
+                self.text.remove(at, at + text.chars().count());
+                let removed = text.matches('\n').count();
+                // the joined line ends like the last one of them
+                if let Some(ref mut line_endings) = self.line_endings {
+                    line_endings.drain(line_number..line_number + removed);
+                }
+                (line_number, removed, 0)
+            },
+        };
+        self.modified = true;
+        self.revision += 1;
+        let text = &self.text;
+        self.line_states.edited(&self.language, line_number, removed, inserted, |line| rope_line(text, line))
+    }
+
+    // Records the edits without committing them, so several replacements
+    // can make up a single change.
+    pub(crate) fn replace_text(&mut self, start: usize, end: usize, text: &str) {
+        if start < end {
+            self.remove_text(start, end);
+        }
+        if !text.is_empty() {
+            self.insert_text(start, text);
+        }
+    }
+
+    /// Undoes the last change, returning where the cursor should be placed,
+    /// or None if there was nothing to undo. Getting back to the saved text
+    /// leaves the buffer unmodified.
+    pub fn undo(&mut self) -> Option<Cursor> {
+        let (edits, cursor) = self.history.undo()?;
+        for edit in &edits {
+            self.apply_edit(edit);
+        }
+        self.modified = !self.history.is_saved();
+        Some(cursor)
+    }
+
+    /// Redoes the last change undone, like undo.
+    pub fn redo(&mut self) -> Option<Cursor> {
+        let (edits, cursor) = self.history.redo()?;
+        for edit in &edits {
+            self.apply_edit(edit);
+        }
+        self.modified = !self.history.is_saved();
+        Some(cursor)
+    }
+}
+
+impl Default for Buffer {
+    fn default() -> Buffer {
+        Buffer::new()
+    }
+}
+
+fn rope_line(text: &Rope, line_number: usize) -> String {
+    if line_number < text.count_newlines() {
+        let line_start = text.line_to_char(line_number);
+        let line_end = text.line_to_char(line_number + 1) - 1;
+        text.slice(line_start, line_end)
+    } else {
+        "".to_string()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use language::Languages;
+
+    fn enums_are_equal(changes: BufferChanges, expected: BufferChanges) -> bool {
+        match (changes, expected) {
+            (BufferChanges::None, BufferChanges::None) => true,
+            (BufferChanges::Buffer, BufferChanges::Buffer) => true,
+            (BufferChanges::Char(pair_0), BufferChanges::Char(pair_1)) => pair_0 == pair_1,
+            (BufferChanges::Lines(vec_0), BufferChanges::Lines(vec_1)) => vec_0 == vec_1,
+            _ => false,
+        }
+    }
+
+    #[test]
+    fn test_initialize_buffer_from_string() {
+        // initialize Buffer from a string
+        let expected_string_0 = "Hello there";
+        let buffer_0 = Buffer::from_string(expected_string_0);
+        assert_eq!(buffer_0.count_lines(), 1);
+        assert_eq!(buffer_0.get_line(0), expected_string_0);
+
+        // intiialize Buffer from a multiline string
+        let expected_string_1 = "Hi there.\nI'm a string.\nMe too!";
+        let buffer_1 = Buffer::from_string(expected_string_1);
+        assert_eq!(buffer_1.count_lines(), 3);
+        assert_eq!(buffer_1.get_line(0), "Hi there.");
+        assert_eq!(buffer_1.get_line(1), "I'm a string.");
+        assert_eq!(buffer_1.get_line(2), "Me too!");
+    }
+
+    #[test]
+    fn test_add_character() {
+        let buffer = Buffer::new();
+
+        // sanity check
+        assert_eq!(buffer.count_lines(), 0);
+
+        // we need a mutable buffer for writing
+        let mut buffer = buffer;
+
+        // write at position (0, 0)
+        let cursor = Cursor::new(0, 0);
+        buffer.write_char(&cursor, 'h');
+        assert_eq!(buffer.count_lines(), 1);
+        assert_eq!(buffer.get_line(0), "h");
+
+        // write at position (0, 1)
+        let cursor = Cursor::new(1, 0);
+        buffer.write_char(&cursor, 'i');
+        assert_eq!(buffer.count_lines(), 1);
+        assert_eq!(buffer.get_line(0), "hi");
+
+        // write at position (10, 10)
+        let cursor = Cursor::new(10, 10);
+        buffer.write_char(&cursor, 'x');
+        assert_eq!(buffer.count_lines(), 11);
+        assert_eq!(buffer.get_line(10), "          x");
+    }
+
+    #[test]
+    fn test_write_character_mid_line() {
+        let mut buffer = Buffer::new();
+        let cursor = Cursor::new(5, 0);
+
+        buffer.write_char(&cursor, 'i');
+        assert_eq!(buffer.count_lines(), 1);
+        assert_eq!(buffer.get_line(0), "     i");
+
+        buffer.write_char(&cursor, 'h');
+        assert_eq!(buffer.count_lines(), 1);
+        assert_eq!(buffer.get_line(0), "     hi");
+    }
+
+    #[test]
+    fn test_insert_newline() {
+        // empty_line
+        let mut buffer = Buffer::new();
+        let cursor = Cursor::new(0, 0);
+        buffer.newline(&cursor);
+        assert_eq!(buffer.count_lines(), 2);
+        assert_eq!(buffer.get_line(0), "");
+        assert_eq!(buffer.get_line(1), "");
+
+        // line with data
+        let mut buffer = Buffer::from_string("Hello world.");
+        let cursor = Cursor::new(5, 0);
+        buffer.newline(&cursor);
+        assert_eq!(buffer.count_lines(), 2);
+        assert_eq!(buffer.get_line(0), "Hello");
+        assert_eq!(buffer.get_line(1), " world.");
+    }
+
+    #[test]
+    #[allow(clippy::bool_assert_comparison)]
+    fn test_delete_one_character() {
+        let mut buffer_0 = Buffer::from_string("I'm a typpo.");
+        // let expected_changes_0 = BufferChanges::Lines(vec![0]);
+        let expected_changes_0 = BufferChanges::Buffer;
+        let cursor = Cursor::new(9, 0);
+        let changes_0 = buffer_0.backspace(&cursor);
+        assert_eq!(true, enums_are_equal(changes_0, expected_changes_0));
+        assert_eq!(buffer_0.count_lines(), 1);
+        assert_eq!(buffer_0.get_line(0), "I'm a typo.");
+
+        // cursor at (0,0), should do nothing
+        let mut buffer_1 = Buffer::from_string("I'm still a tipo");
+        let expected_changes_1 = BufferChanges::None;
+        let cursor = Cursor::new(0, 0);
+        let changes_1 = buffer_1.backspace(&cursor);
+        assert_eq!(true, enums_are_equal(changes_1, expected_changes_1));
+        assert_eq!(buffer_1.count_lines(), 1);
+        assert_eq!(buffer_1.get_line(0), "I'm still a tipo");
+    }
+
+    #[test]
+    fn test_delete_from_first_position_in_line() {
+        let mut buffer = Buffer::from_string("Line 1\nA\nLine 3");
+        assert_eq!(buffer.count_lines(), 3);
+        let cursor = Cursor::new(1, 1);
+        buffer.backspace(&cursor);
+        assert_eq!(buffer.count_lines(), 3);
+        let cursor = Cursor::new(0, 1);
+        buffer.backspace(&cursor);
+        assert_eq!(buffer.count_lines(), 2);
+
+        assert_eq!(buffer.get_line(0), "Line 1");
+        assert_eq!(buffer.get_line(1), "Line 3");
+    }
+
+    #[test]
+    fn test_backspace_line_content_go_up() {
+        let mut buffer = Buffer::from_string("Something\nElse");
+        let cursor = Cursor::new(0, 1);
+        assert_eq!(buffer.count_lines(), 2);
+        buffer.backspace(&cursor);
+        assert_eq!(buffer.count_lines(), 1);
+        assert_eq!(buffer.get_line(0), "SomethingElse");
+    }
+
+    #[test]
+    fn test_copy_region() {
+        let buffer = Buffer::from_string("Line 0\nLine 1\nLine 2");
+        let region = Region::new(Cursor::new(2, 0), Cursor::new(4, 0));
+        assert_eq!(buffer.copy_region(&region), "ne");
+
+        let region = Region::new(Cursor::new(5, 2), Cursor::new(4, 0));
+        assert_eq!(buffer.copy_region(&region), " 0\nLine 1\nLine ");
+
+        // positions past the end of a line or the buffer are clamped
+        let region = Region::new(Cursor::new(20, 1), Cursor::new(3, 10));
+        assert_eq!(buffer.copy_region(&region), "\nLine 2\n");
+    }
+
+    #[test]
+    fn test_cut_region_multiline() {
+        let mut buffer = Buffer::from_string("Line 0\nLine 1\nLine 2");
+        let region = Region::new(Cursor::new(4, 0), Cursor::new(4, 2));
+        let (changes, text) = buffer.cut_region(&region);
+        assert!(enums_are_equal(changes, BufferChanges::Buffer));
+        assert_eq!(text, " 0\nLine 1\nLine");
+        assert_eq!(buffer.count_lines(), 1);
+        assert_eq!(buffer.get_line(0), "Line 2");
+
+        // undo brings it back and puts the cursor at the end of the region
+        assert_eq!(buffer.undo(), Some(Cursor::new(4, 2)));
+        assert_eq!(buffer.count_lines(), 3);
+        assert_eq!(buffer.get_line(1), "Line 1");
+    }
+
+    #[test]
+    fn test_delete_empty_region() {
+        let mut buffer = Buffer::from_string("Line 0");
+        let region = Region::new(Cursor::new(3, 0), Cursor::new(3, 0));
+        let changes = buffer.delete_region(&region);
+        assert!(enums_are_equal(changes, BufferChanges::None));
+        assert_eq!(buffer.get_line(0), "Line 0");
+        assert_eq!(buffer.undo(), None);
+    }
+
+    #[test]
+    fn test_insert_string() {
+        let mut buffer = Buffer::from_string("Hello world");
+        let (changes, cursor) = buffer.insert_string(&Cursor::new(5, 0), ",");
+        assert!(enums_are_equal(changes, BufferChanges::Lines(vec![0])));
+        assert_eq!(cursor, Cursor::new(6, 0));
+        assert_eq!(buffer.get_line(0), "Hello, world");
+
+        let (changes, cursor) = buffer.insert_string(&Cursor::new(6, 0), "\nbig\nwide");
+        assert!(enums_are_equal(changes, BufferChanges::Buffer));
+        assert_eq!(cursor, Cursor::new(4, 2));
+        assert_eq!(buffer.count_lines(), 3);
+        assert_eq!(buffer.get_line(0), "Hello,");
+        assert_eq!(buffer.get_line(1), "big");
+        assert_eq!(buffer.get_line(2), "wide world");
+
+        // a paste is a single undo step
+        assert_eq!(buffer.undo(), Some(Cursor::new(6, 0)));
+        assert_eq!(buffer.count_lines(), 1);
+        assert_eq!(buffer.get_line(0), "Hello, world");
+    }
+
+    #[test]
+    fn test_cut_and_paste_round_trip() {
+        let mut buffer = Buffer::from_string("one\ntwo\nthree");
+        let region = Region::new(Cursor::new(1, 0), Cursor::new(2, 2));
+        let (_, text) = buffer.cut_region(&region);
+        assert_eq!(buffer.get_line(0), "oree");
+
+        let (_, cursor) = buffer.insert_string(&region.start, &text);
+        assert_eq!(cursor, region.end);
+        assert_eq!(buffer.count_lines(), 3);
+        assert_eq!(buffer.get_line(0), "one");
+        assert_eq!(buffer.get_line(1), "two");
+        assert_eq!(buffer.get_line(2), "three");
+    }
+
+    #[test]
+    fn test_replace_region() {
+        let mut buffer = Buffer::from_string("one\ntwo\nthree");
+        let region = Region::new(Cursor::new(1, 0), Cursor::new(2, 2));
+        let (changes, cursor) = buffer.replace_region(&region, "ld\nb");
+        assert!(enums_are_equal(changes, BufferChanges::Buffer));
+        assert_eq!(cursor, Cursor::new(1, 1));
+        assert_eq!(buffer.count_lines(), 2);
+        assert_eq!(buffer.get_line(0), "old");
+        assert_eq!(buffer.get_line(1), "bree");
+
+        // one undo brings the region back
+        assert_eq!(buffer.undo(), Some(Cursor::new(2, 2)));
+        assert_eq!(buffer.count_lines(), 3);
+        assert_eq!(buffer.get_line(0), "one");
+        assert_eq!(buffer.get_line(1), "two");
+        assert_eq!(buffer.get_line(2), "three");
+    }
+
+    #[test]
+    fn test_typing_a_comment_restyles_following_lines() {
+        let mut buffer = Buffer::from_string("a\nb\nc\n");
+        buffer.set_language(Languages::built_in().detect("main.rs", ""));
+        buffer.highlight_to(10);
+        buffer.write_char(&Cursor::new(1, 0), '/');
+        let changes = buffer.write_char(&Cursor::new(2, 0), '*');
+        assert!(enums_are_equal(changes, BufferChanges::Lines(vec![0, 1, 2, 3])));
+        assert_eq!(buffer.line_state(2), State::BlockComment(1));
+
+        // undoing goes through the same bookkeeping
+        buffer.undo();
+        assert_eq!(buffer.get_line(0), "a");
+        assert_eq!(buffer.line_state(2), State::Normal);
+        let changes = buffer.write_char(&Cursor::new(1, 1), 'x');
+        assert!(enums_are_equal(changes, BufferChanges::Lines(vec![1])));
+    }
+
+    #[test]
+    fn test_modified_flag() {
+        let mut buffer = Buffer::from_string("text");
+        assert!(!buffer.modified);
+        buffer.write_char(&Cursor::new(0, 0), 'x');
+        assert!(buffer.modified);
+        buffer.mark_saved();
+        buffer.undo();
+        assert!(buffer.modified);
+        // back to the saved text
+        buffer.redo();
+        assert!(!buffer.modified);
+    }
+
+    fn saved(buffer: &Buffer) -> String {
+        let mut file = Vec::new();
+        buffer.write_to(&mut file).unwrap();
+        String::from_utf8(file).unwrap()
+    }
+
+    #[test]
+    fn test_line_endings_round_trip() {
+        for text in &["a\nb\n", "a\nb", "a\r\nb\r\n", "a\r\n\r\nb", "", "\n", "\r\n"] {
+            assert_eq!(&saved(&Buffer::from_string(text)), text);
+        }
+        // an empty file gets a final newline, like a new one
+        let mut buffer = Buffer::from_string("");
+        buffer.write_char(&Cursor::new(0, 0), 'a');
+        assert_eq!(saved(&buffer), "a\n");
+
+        // new lines get the file's ending too
+        let mut buffer = Buffer::from_string("a\r\nb");
+        buffer.newline(&Cursor::new(1, 0));
+        assert_eq!(saved(&buffer), "a\r\n\r\nb");
+    }
+
+    #[test]
+    fn test_mixed_line_endings_round_trip() {
+        for text in &["a\nb\r\nc\n", "a\r\nb\nc", "a\nb\r\n", "\r\n\n"] {
+            let buffer = Buffer::from_string(text);
+            assert!(buffer.has_mixed_line_endings());
+            assert_eq!(&saved(&buffer), text);
+        }
+
+        // lines keep their endings through edits, new ones end like the first
+        let mut buffer = Buffer::from_string("a\nb\r\nc\n");
+        buffer.newline(&Cursor::new(1, 1));
+        assert_eq!(saved(&buffer), "a\nb\n\r\nc\n");
+        buffer.backspace(&Cursor::new(0, 1));
+        assert_eq!(saved(&buffer), "ab\n\r\nc\n");
+
+        // until they're converted
+        assert!(buffer.set_line_ending(LineEnding::Lf));
+        assert!(!buffer.has_mixed_line_endings());
+        assert_eq!(saved(&buffer), "ab\n\nc\n");
+    }
+
+    #[test]
+    fn test_convert_line_endings() {
+        let mut buffer = Buffer::from_string("a\nb\n");
+        assert!(!buffer.set_line_ending(LineEnding::Lf));
+        assert!(!buffer.modified);
+        assert!(buffer.set_line_ending(LineEnding::Crlf));
+        assert!(buffer.modified);
+        assert_eq!(saved(&buffer), "a\r\nb\r\n");
+        buffer.set_line_ending(LineEnding::Lf);
+        assert_eq!(saved(&buffer), "a\nb\n");
+    }
+}
@@ -0,0 +1,289 @@
+//! Clipboard for cut/copy/paste.
+//!
+//! Text always goes to an in-process register. Optionally it's also handed to
+//! the system clipboard, either through the OSC 52 escape sequence (the
+//! terminal does the copying, works over ssh) or through external commands
+//! like xclip or wl-copy. Which one is picked from the environment:
+//!
+//! ```text
+//! EDITOR2_CLIPBOARD=osc52|xclip|wayland
+//! EDITOR2_CLIPBOARD_COPY="some command"   (reads the text from stdin)
+//! EDITOR2_CLIPBOARD_PASTE="some command"  (writes the text to stdout)
+//! ```
+
+use std::env;
+use std::io::{self, Write};
+use std::process::{Command, Stdio};
+
+pub trait SystemClipboard {
+    fn copy(&mut self, text: &str) -> io::Result<()>;
+    // None when this clipboard can't be read from
+    fn paste(&mut self) -> io::Result<Option<String>>;
+}
+
+/// Asks the terminal to set its clipboard. Terminals don't let us read it
+/// back without a round trip through the input, so pasting isn't supported.
+pub struct Osc52<W: Write> {
+    out: W,
+}
+
+impl<W: Write> Osc52<W> {
+    pub fn new(out: W) -> Osc52<W> {
+        Osc52 {out}
+    }
+}
+
+impl<W: Write> SystemClipboard for Osc52<W> {
+    fn copy(&mut self, text: &str) -> io::Result<()> {
+        write!(self.out, "\x1b]52;c;{}\x07", base64(text.as_bytes()))?;
+        self.out.flush()
+    }
+
+    fn paste(&mut self) -> io::Result<Option<String>> {
+        Ok(None)
+    }
+}
+
+/// Pipes the text through external programs, given as argv.
+pub struct CommandClipboard {
+    copy_command: Vec<String>,
+    paste_command: Option<Vec<String>>,
+}
+
+impl CommandClipboard {
+    pub fn new(copy_command: Vec<String>, paste_command: Option<Vec<String>>) -> CommandClipboard {
+        CommandClipboard {copy_command, paste_command}
+    }
+
+    pub fn xclip() -> CommandClipboard {
+        CommandClipboard::new(argv(&["xclip", "-selection", "clipboard"]),
+                              Some(argv(&["xclip", "-selection", "clipboard", "-o"])))
+    }
+
+    pub fn wayland() -> CommandClipboard {
+        CommandClipboard::new(argv(&["wl-copy"]),
+                              Some(argv(&["wl-paste", "--no-newline"])))
+    }
+
+    /// commands given as a single string are run by the shell
+    pub fn shell(copy_command: &str, paste_command: Option<&str>) -> CommandClipboard {
+        CommandClipboard::new(argv(&["sh", "-c", copy_command]),
+                              paste_command.map(|command| argv(&["sh", "-c", command])))
+    }
+}
+
+fn argv(arguments: &[&str]) -> Vec<String> {
+    arguments.iter().map(|argument| argument.to_string()).collect()
+}
+
+fn failed(command: &[String]) -> io::Error {
+    io::Error::other(format!("{} failed", command[0]))
+}
+
+impl SystemClipboard for CommandClipboard {
+    fn copy(&mut self, text: &str) -> io::Result<()> {
+        let mut child = Command::new(&self.copy_command[0])
+            .args(&self.copy_command[1..])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(text.as_bytes())?;
+        }
+        if child.wait()?.success() {
+            Ok(())
+        } else {
+            Err(failed(&self.copy_command))
+        }
+    }
+
+    fn paste(&mut self) -> io::Result<Option<String>> {
+        let command = match self.paste_command {
+            Some(ref command) => command,
+            None => return Ok(None),
+        };
+        let output = Command::new(&command[0])
+            .args(&command[1..])
+            .stdin(Stdio::null())
+            .stderr(Stdio::null())
+            .output()?;
+        if output.status.success() {
+            Ok(Some(String::from_utf8_lossy(&output.stdout).into_owned()))
+        } else {
+            Err(failed(command))
+        }
+    }
+}
+
+pub struct Clipboard {
+    register: String,
+    system: Option<Box<dyn SystemClipboard>>,
+}
+
+impl Clipboard {
+    pub fn new() -> Clipboard {
+        Clipboard {register: String::new(), system: None}
+    }
+
+    pub fn with_system(system: Box<dyn SystemClipboard>) -> Clipboard {
+        Clipboard {register: String::new(), system: Some(system)}
+    }
+
+    pub fn from_env() -> Clipboard {
+        let copy_command = env::var("EDITOR2_CLIPBOARD_COPY").ok();
+        let paste_command = env::var("EDITOR2_CLIPBOARD_PASTE").ok();
+        if let Some(copy_command) = copy_command {
+            let system = CommandClipboard::shell(&copy_command, paste_command.as_ref().map(|c| &c[..]));
+            return Clipboard::with_system(Box::new(system));
+        }
+
+        match env::var("EDITOR2_CLIPBOARD").as_ref().map(|name| &name[..]) {
+            Ok("osc52")   => Clipboard::with_system(Box::new(Osc52::new(io::stdout()))),
+            Ok("xclip")   => Clipboard::with_system(Box::new(CommandClipboard::xclip())),
+            Ok("wayland") => Clipboard::with_system(Box::new(CommandClipboard::wayland())),
+            _             => Clipboard::new(),
+        }
+    }
+
+    /// The register is always updated, even if the system clipboard fails.
+    pub fn copy(&mut self, text: String) -> io::Result<()> {
+        let result = match self.system {
+            Some(ref mut system) => system.copy(&text),
+            None => Ok(()),
+        };
+        self.register = text;
+        result
+    }
+
+    /// Prefers the system clipboard, falls back to the register when there's
+    /// none, it can't be read from or it fails.
+    pub fn paste(&mut self) -> String {
+        if let Some(ref mut system) = self.system {
+            if let Ok(Some(text)) = system.paste() {
+                return text;
+            }
+        }
+        self.register.clone()
+    }
+}
+
+impl Default for Clipboard {
+    fn default() -> Clipboard {
+        Clipboard::new()
+    }
+}
+
+const BASE64_ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn base64(input: &[u8]) -> String {
+    let mut result = String::with_capacity(input.len().div_ceil(3) * 4);
+    for chunk in input.chunks(3) {
+        let bytes = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let group = (bytes[0] as u32) << 16 | (bytes[1] as u32) << 8 | bytes[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                let index = (group >> (18 - 6 * i)) & 0x3f;
+                result.push(BASE64_ALPHABET[index as usize] as char);
+            } else {
+                result.push('=');
+            }
+        }
+    }
+    result
+}
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use std::cell::RefCell;
+    use std::fs;
+    use std::rc::Rc;
+
+    // remembers what was copied, pastes whatever it was given
+    struct FakeClipboard {
+        copied: Rc<RefCell<Vec<String>>>,
+        contents: Option<String>,
+        fail: bool,
+    }
+
+    impl SystemClipboard for FakeClipboard {
+        fn copy(&mut self, text: &str) -> io::Result<()> {
+            if self.fail {
+                return Err(io::Error::other("nope"));
+            }
+            self.copied.borrow_mut().push(text.to_string());
+            Ok(())
+        }
+
+        fn paste(&mut self) -> io::Result<Option<String>> {
+            if self.fail {
+                return Err(io::Error::other("nope"));
+            }
+            Ok(self.contents.clone())
+        }
+    }
+
+    #[test]
+    fn test_base64() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+        assert_eq!(base64("línea\n".as_bytes()), "bMOtbmVhCg==");
+    }
+
+    #[test]
+    fn test_osc52_writes_escape_sequence() {
+        let mut osc52 = Osc52::new(Vec::new());
+        osc52.copy("foo").unwrap();
+        assert_eq!(osc52.out, b"\x1b]52;c;Zm9v\x07");
+        assert_eq!(osc52.paste().unwrap(), None);
+    }
+
+    #[test]
+    fn test_register_without_system_clipboard() {
+        let mut clipboard = Clipboard::new();
+        assert_eq!(clipboard.paste(), "");
+        clipboard.copy("two\nlines".to_string()).unwrap();
+        assert_eq!(clipboard.paste(), "two\nlines");
+    }
+
+    #[test]
+    fn test_system_clipboard_is_preferred() {
+        let copied = Rc::new(RefCell::new(Vec::new()));
+        let fake = FakeClipboard {copied: copied.clone(), contents: Some("system".to_string()), fail: false};
+        let mut clipboard = Clipboard::with_system(Box::new(fake));
+        clipboard.copy("mine".to_string()).unwrap();
+        assert_eq!(*copied.borrow(), vec!["mine".to_string()]);
+        assert_eq!(clipboard.paste(), "system");
+    }
+
+    #[test]
+    fn test_failing_system_clipboard_falls_back_to_register() {
+        let copied = Rc::new(RefCell::new(Vec::new()));
+        let fake = FakeClipboard {copied: copied.clone(), contents: None, fail: true};
+        let mut clipboard = Clipboard::with_system(Box::new(fake));
+        assert!(clipboard.copy("mine".to_string()).is_err());
+        assert_eq!(clipboard.paste(), "mine");
+    }
+
+    #[test]
+    fn test_command_clipboard_round_trip() {
+        // a fake clipboard tool that keeps its contents in a file
+        let path = env::temp_dir().join(format!("editor2-clipboard-{}", std::process::id()));
+        let path = path.to_str().unwrap();
+        let mut clipboard = CommandClipboard::shell(&format!("cat > '{}'", path),
+                                                    Some(&format!("cat '{}'", path)));
+        clipboard.copy("one\ntwo\n").unwrap();
+        assert_eq!(clipboard.paste().unwrap(), Some("one\ntwo\n".to_string()));
+        let _ = fs::remove_file(path);
+
+        let mut broken = CommandClipboard::shell("exit 1", None);
+        assert!(broken.copy("text").is_err());
+        assert_eq!(broken.paste().unwrap(), None);
+    }
+}
@@ -0,0 +1,371 @@
+//! What keys do to a buffer.
+//!
+//! These are the editing commands that only need a buffer and a cursor, so
+//! they work the same with or without a screen. The editor adds the ones
+//! that need more, like searching or the clipboard, on top.
+
+use backend::Key;
+use buffer::{Buffer, BufferChanges};
+use cursor::Cursor;
+use wrap;
+
+/// Where the cursor goes with an arrow key. With `wrap_width`, lines are
+/// wrapped to that width and Up/Down move by screen rows instead of lines.
+pub fn get_next_cursor(current_cursor: &Cursor, buffer: &Buffer, direction: Key,
+                       wrap_width: Option<usize>) -> Cursor {
+    let &Cursor{x, y} = current_cursor;
+
+    if let Some(width) = wrap_width {
+        if direction == Key::Up || direction == Key::Down {
+            return wrap::next_visual_cursor(current_cursor, buffer, direction == Key::Up, width);
+        }
+    }
+
+    let valid_movement: bool = match (x, y, direction) {
+        // We can only go up if we're somewhere other than the first line
+        (_, y, Key::Up) if y > 0 => true,
+        // We can only go down if there's more lines in the buffer "below"
+        (_, y, Key::Down) if y + 1 < buffer.count_lines() => true,
+        // Valid left movements are when in the middle of a line or at the
+        // beginning of a line other than the first one
+        (x, y, Key::Left) if x > 0 || y > 0 => true,
+        // We can only go right if we haven't reach the end of the last line
+        (x, y, Key::Right) if x < buffer.get_line_length(y) || y < buffer.count_lines() => true,
+        _ => false
+    };
+
+    if !valid_movement { return Cursor::new(x, y); }
+
+    match direction {
+        Key::Left  => {
+            // if we're at the beginning of a line, jump back to the previous
+            // one if possible
+            if y > 0 && x == 0 {
+                Cursor::new(buffer.get_line_length(y-1), y-1)
+            } else {
+                Cursor::new(x-1, y)
+            }
+        },
+        Key::Right => {
+            // if we're at the end of a line, jump to the beginning of the next
+            // one
+            if y + 1 < buffer.count_lines() && x == buffer.get_line_length(y) {
+                Cursor::new(0, y+1)
+            } else {
+                Cursor::new(x+1, y)
+            }
+        },
+        Key::Up    => {
+            // if previous line's length is lower than x, go to its EOL
+            if buffer.get_line_length(y-1) < x {
+                Cursor::new(buffer.get_line_length(y-1), y-1)
+            } else {
+                Cursor::new(x, y-1)
+            }
+        }
+        Key::Down  => {
+            // if next line's length is lower than x, go to its EOL
+            if buffer.get_line_length(y+1) < x {
+                Cursor::new(buffer.get_line_length(y+1), y+1)
+            } else {
+                Cursor::new(x, y+1)
+            }
+        },
+        _          => unreachable!()
+    }
+}
+
+/// Applies an editing key to the buffer: typing, Enter, Backspace, undo
+/// (Ctrl-Z) and redo (Ctrl-Y). Returns what changed and where the cursor
+/// ends up; other keys change nothing.
+pub fn apply_command(key: Key, buffer: &mut Buffer, cursor: &Cursor) -> (BufferChanges, Cursor) {
+    match key {
+        Key::Char(character) => {
+            (buffer.write_char(cursor, character), Cursor::new(cursor.x + 1, cursor.y))
+        },
+        Key::Enter           => {
+            let buffer_changes = buffer.newline(cursor);
+            let new_cursor = Cursor::new(0, cursor.y + 1);
+            (buffer_changes, new_cursor)
+        },
+        Key::Backspace       => {
+            let previous_line_length = if cursor.y > 0 {
+                buffer.get_line_length(cursor.y-1)
+            } else {
+                0
+            };
+
+            let changes = buffer.backspace(cursor);
+
+            let new_cursor = if cursor.x > 0 {
+                Cursor::new(cursor.x - 1, cursor.y)
+            } else if cursor.y > 0 {
+                Cursor::new(previous_line_length, cursor.y - 1)
+            } else {
+                Cursor::new(cursor.x, cursor.y)
+            };
+
+            (changes, new_cursor)
+        }
+        Key::Ctrl('z')       => {
+            match buffer.undo() {
+                Some(new_cursor) => (BufferChanges::Buffer, new_cursor),
+                None             => (BufferChanges::None, *cursor),
+            }
+        }
+        Key::Ctrl('y')       => {
+            match buffer.redo() {
+                Some(new_cursor) => (BufferChanges::Buffer, new_cursor),
+                None             => (BufferChanges::None, *cursor),
+            }
+        }
+        _ => {(BufferChanges::None, Cursor::new(cursor.x, cursor.y))}
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use buffer::LineEnding;
+
+    #[test]
+    fn test_cursor_movements_off_limits() {
+        let empty_buffer = Buffer::new();
+        let original_cursor = Cursor::new(0, 0);
+
+        // try to go down, nothing happens
+        let next_cursor = get_next_cursor(&original_cursor, &empty_buffer, Key::Down, None);
+        assert_eq!(next_cursor.x, original_cursor.x);
+        assert_eq!(next_cursor.y, original_cursor.y);
+
+        // try to go up, nothing happens
+        let next_cursor = get_next_cursor(&original_cursor, &empty_buffer, Key::Up, None);
+        assert_eq!(next_cursor.x, original_cursor.x);
+        assert_eq!(next_cursor.y, original_cursor.y);
+
+        // try to go left, nothing happens
+        let next_cursor = get_next_cursor(&original_cursor, &empty_buffer, Key::Left, None);
+        assert_eq!(next_cursor.x, original_cursor.x);
+        assert_eq!(next_cursor.y, original_cursor.y);
+
+        // try to go right, nothing happens
+        let next_cursor = get_next_cursor(&original_cursor, &empty_buffer, Key::Right, None);
+        assert_eq!(next_cursor.x, original_cursor.x);
+        assert_eq!(next_cursor.y, original_cursor.y);
+    }
+
+    #[test]
+    fn test_cursor_movements_off_limits_exceptions() {
+        let line_0 = "I'm Line 0";
+        let line_1 = "And here's Line 1.";
+        let line_2 = "Line 1 here";
+        let buffer = Buffer::from_string(&[line_0, line_1, line_2].join("\n"));
+
+        // Moving left at the beginning of a line should make the cursor
+        // jump to the last character of previous line.
+        let original_cursor = Cursor::new(0, 1);
+        let expected_cursor = Cursor::new(line_0.len(), 0);
+        let next_cursor = get_next_cursor(&original_cursor, &buffer, Key::Left, None);
+        assert_eq!(next_cursor.x, expected_cursor.x);
+        assert_eq!(next_cursor.y, expected_cursor.y);
+
+        // Moving right at the end of a line should make the cursor
+        // jump to the first character of the next line.
+        let original_cursor = Cursor::new(line_1.len(), 1);
+        let expected_cursor = Cursor::new(0, 2);
+        let next_cursor = get_next_cursor(&original_cursor, &buffer, Key::Right, None);
+        assert_eq!(next_cursor.x, expected_cursor.x);
+        assert_eq!(next_cursor.y, expected_cursor.y);
+
+        // Moving down when the next line is shorter, should move the cursor to EOL
+        let original_cursor = Cursor::new(line_1.len(), 1);
+        let expected_cursor = Cursor::new(line_2.len(), 2);
+        let next_cursor = get_next_cursor(&original_cursor, &buffer, Key::Down, None);
+        assert_eq!(next_cursor.x, expected_cursor.x);
+        assert_eq!(next_cursor.y, expected_cursor.y);
+
+        // Moving up when the previous line is shorter, should move the cursor to EOL
+        let original_cursor = Cursor::new(line_1.len(), 1);
+        let expected_cursor = Cursor::new(line_0.len(), 0);
+        let next_cursor = get_next_cursor(&original_cursor, &buffer, Key::Up, None);
+        assert_eq!(next_cursor.x, expected_cursor.x);
+        assert_eq!(next_cursor.y, expected_cursor.y);
+
+    }
+
+    #[test]
+    fn test_cursor_movements_happy_path() {
+        let buffer = Buffer::from_string("I'm Line 0\nLine 1 here\nAnd here's Line 2.");
+
+        // move from the middle of the line to the left
+        let original_cursor = Cursor::new(3, 0);
+        let expected_cursor = Cursor::new(original_cursor.x - 1, original_cursor.y);
+        let next_cursor = get_next_cursor(&original_cursor, &buffer, Key::Left, None);
+        assert_eq!(next_cursor.x, expected_cursor.x);
+        assert_eq!(next_cursor.y, expected_cursor.y);
+
+        // move from the middle of the line to the right
+        let original_cursor = Cursor::new(3, 0);
+        let expected_cursor = Cursor::new(original_cursor.x + 1, original_cursor.y);
+        let next_cursor = get_next_cursor(&original_cursor, &buffer, Key::Right, None);
+        assert_eq!(next_cursor.x, expected_cursor.x);
+        assert_eq!(next_cursor.y, expected_cursor.y);
+
+        // move up
+        let original_cursor = Cursor::new(3, 1);
+        let expected_cursor = Cursor::new(original_cursor.x, original_cursor.y-1);
+        let next_cursor = get_next_cursor(&original_cursor, &buffer, Key::Up, None);
+        assert_eq!(next_cursor.x, expected_cursor.x);
+        assert_eq!(next_cursor.y, expected_cursor.y);
+
+        // move down
+        let original_cursor = Cursor::new(3, 1);
+        let expected_cursor = Cursor::new(original_cursor.x, original_cursor.y+1);
+        let next_cursor = get_next_cursor(&original_cursor, &buffer, Key::Down, None);
+        assert_eq!(next_cursor.x, expected_cursor.x);
+        assert_eq!(next_cursor.y, expected_cursor.y);
+    }
+
+    #[test]
+    fn test_undo_groups_typed_characters() {
+        let mut buffer = Buffer::from_string("Hello");
+        let mut cursor = Cursor::new(5, 0);
+        for character in " world".chars() {
+            let (_, new_cursor) = apply_command(Key::Char(character), &mut buffer, &cursor);
+            cursor = new_cursor;
+        }
+        assert_eq!(buffer.get_line(0), "Hello world");
+
+        // a single undo takes back the whole word
+        let (_, cursor) = apply_command(Key::Ctrl('z'), &mut buffer, &cursor);
+        assert_eq!(buffer.get_line(0), "Hello");
+        assert_eq!(cursor, Cursor::new(5, 0));
+
+        // and there's nothing else to undo
+        let (changes, cursor) = apply_command(Key::Ctrl('z'), &mut buffer, &cursor);
+        assert_eq!(changes, BufferChanges::None);
+        assert_eq!(buffer.get_line(0), "Hello");
+        assert_eq!(cursor, Cursor::new(5, 0));
+    }
+
+    #[test]
+    fn test_undo_breaks_groups_on_other_commands() {
+        let mut buffer = Buffer::new();
+        let mut cursor = Cursor::new(0, 0);
+        for key in [Key::Char('a'), Key::Char('b'), Key::Enter,
+                        Key::Char('c'), Key::Backspace, Key::Char('d')] {
+            let (_, new_cursor) = apply_command(key, &mut buffer, &cursor);
+            cursor = new_cursor;
+        }
+        assert_eq!(buffer.get_line(0), "ab");
+        assert_eq!(buffer.get_line(1), "d");
+
+        let (_, cursor) = apply_command(Key::Ctrl('z'), &mut buffer, &cursor);
+        assert_eq!(buffer.get_line(1), "");
+        assert_eq!(cursor, Cursor::new(0, 1));
+
+        let (_, cursor) = apply_command(Key::Ctrl('z'), &mut buffer, &cursor);
+        assert_eq!(buffer.get_line(1), "c");
+        assert_eq!(cursor, Cursor::new(1, 1));
+
+        let (_, cursor) = apply_command(Key::Ctrl('z'), &mut buffer, &cursor);
+        let (_, cursor) = apply_command(Key::Ctrl('z'), &mut buffer, &cursor);
+        assert_eq!(buffer.count_lines(), 1);
+        assert_eq!(buffer.get_line(0), "ab");
+        assert_eq!(cursor, Cursor::new(2, 0));
+
+        // the first write also created the line, undoing it leaves nothing
+        let (_, cursor) = apply_command(Key::Ctrl('z'), &mut buffer, &cursor);
+        assert_eq!(buffer.count_lines(), 0);
+        assert_eq!(cursor, Cursor::new(0, 0));
+    }
+
+    #[test]
+    fn test_redo_after_undo() {
+        let mut buffer = Buffer::from_string("Line 1\nLine 2");
+        let cursor = Cursor::new(0, 1);
+        let (_, cursor) = apply_command(Key::Backspace, &mut buffer, &cursor);
+        assert_eq!(buffer.get_line(0), "Line 1Line 2");
+        assert_eq!(cursor, Cursor::new(6, 0));
+
+        let (_, cursor) = apply_command(Key::Ctrl('z'), &mut buffer, &cursor);
+        assert_eq!(buffer.count_lines(), 2);
+        assert_eq!(cursor, Cursor::new(0, 1));
+
+        let (changes, cursor) = apply_command(Key::Ctrl('y'), &mut buffer, &cursor);
+        assert_eq!(changes, BufferChanges::Buffer);
+        assert_eq!(buffer.get_line(0), "Line 1Line 2");
+        assert_eq!(cursor, Cursor::new(6, 0));
+    }
+
+    #[test]
+    fn test_new_edit_invalidates_redo() {
+        let mut buffer = Buffer::from_string("abc");
+        let cursor = Cursor::new(3, 0);
+        let (_, cursor) = apply_command(Key::Char('d'), &mut buffer, &cursor);
+        let (_, cursor) = apply_command(Key::Ctrl('z'), &mut buffer, &cursor);
+        assert_eq!(buffer.get_line(0), "abc");
+
+        // typing after an undo must not be merged with the undone change,
+        // and drops it from the redo stack
+        let (_, cursor) = apply_command(Key::Char('x'), &mut buffer, &cursor);
+        assert_eq!(buffer.get_line(0), "abcx");
+        let (changes, cursor) = apply_command(Key::Ctrl('y'), &mut buffer, &cursor);
+        assert_eq!(changes, BufferChanges::None);
+        assert_eq!(buffer.get_line(0), "abcx");
+        assert_eq!(cursor, Cursor::new(4, 0));
+
+        apply_command(Key::Ctrl('z'), &mut buffer, &cursor);
+        assert_eq!(buffer.get_line(0), "abc");
+    }
+
+    #[test]
+    fn test_undo_to_saved_state() {
+        let mut buffer = Buffer::from_string("text");
+        let cursor = Cursor::new(4, 0);
+        let (_, cursor) = apply_command(Key::Char('s'), &mut buffer, &cursor);
+        assert!(buffer.modified);
+        let (_, cursor) = apply_command(Key::Ctrl('z'), &mut buffer, &cursor);
+        assert!(!buffer.modified);
+
+        // typing right after saving doesn't join the saved change
+        let (_, cursor) = apply_command(Key::Char('s'), &mut buffer, &cursor);
+        buffer.mark_saved();
+        let (_, cursor) = apply_command(Key::Char('!'), &mut buffer, &cursor);
+        let (_, cursor) = apply_command(Key::Ctrl('z'), &mut buffer, &cursor);
+        assert_eq!(buffer.get_line(0), "texts");
+        assert!(!buffer.modified);
+
+        // once a new change replaces the saved one it can't be reached
+        let (_, cursor) = apply_command(Key::Ctrl('z'), &mut buffer, &cursor);
+        let (_, cursor) = apply_command(Key::Char('?'), &mut buffer, &cursor);
+        apply_command(Key::Ctrl('z'), &mut buffer, &cursor);
+        assert_eq!(buffer.get_line(0), "text");
+        assert!(buffer.modified);
+
+        // nor can undoing switch the line endings back
+        let mut buffer = Buffer::from_string("text\n");
+        let (_, cursor) = apply_command(Key::Char('s'), &mut buffer, &Cursor::new(4, 0));
+        buffer.set_line_ending(LineEnding::Crlf);
+        apply_command(Key::Ctrl('z'), &mut buffer, &cursor);
+        assert!(buffer.modified);
+    }
+
+    // #[test]
+    // fn test_backspace_at_0_0_should_do_nothing(){
+    // }
+
+    // #[test]
+    // fn test_cursor_goes_previous_eol_after_slurping_line() {
+    // }
+
+    // #[test]
+    // fn moving_up_or_down_to_a_smaller_lines_moves_cursor_to_eol() {
+    // }
+
+    // #[test]
+    // fn prevent_moving_cursor_beyond_eol() {
+    // }
+}
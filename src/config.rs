@@ -0,0 +1,215 @@
+// Configuration files.
+//
+// Syntax definitions and themes are written in a small part of TOML: keys
+// set to strings, booleans or arrays of strings, and comments. That's all we
+// understand, tables and numbers aren't needed.
+
+use std::env;
+use std::path::{Path, PathBuf};
+
+pub enum Value {
+    Text(String),
+    Bool(bool),
+    List(Vec<String>),
+}
+
+// where the user's configuration lives
+pub fn config_dir() -> Option<PathBuf> {
+    env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))
+        .map(|dir| dir.join("editor2"))
+}
+
+struct Parser {
+    characters: Vec<char>,
+    position: usize,
+    line: usize,
+}
+
+// The key/value pairs of a definition, with the line each one is on.
+pub fn parse(text: &str) -> Result<Vec<(usize, String, Value)>, String> {
+    let mut parser = Parser {characters: text.chars().collect(), position: 0, line: 1};
+    let mut pairs = Vec::new();
+    loop {
+        parser.skip_blank();
+        if parser.peek().is_none() {
+            return Ok(pairs);
+        }
+        let line = parser.line;
+        let pair = parser.pair().map_err(|error| format!("line {}: {}", parser.line, error))?;
+        pairs.push((line, pair.0, pair.1));
+    }
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.characters.get(self.position).cloned()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let character = self.peek();
+        if character == Some('\n') {
+            self.line += 1;
+        }
+        self.position += 1;
+        character
+    }
+
+    fn skip_spaces(&mut self) {
+        while self.peek() == Some(' ') || self.peek() == Some('\t') {
+            self.next();
+        }
+    }
+
+    fn skip_comment(&mut self) {
+        if self.peek() == Some('#') {
+            while self.peek().is_some_and(|character| character != '\n') {
+                self.next();
+            }
+        }
+    }
+
+    // spaces, newlines and comments
+    fn skip_blank(&mut self) {
+        loop {
+            self.skip_spaces();
+            self.skip_comment();
+            match self.peek() {
+                Some('\n') | Some('\r') => { self.next(); },
+                _ => return,
+            }
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        match self.next() {
+            Some(character) if character == expected => Ok(()),
+            _ => Err(format!("expected {}", expected)),
+        }
+    }
+
+    fn pair(&mut self) -> Result<(String, Value), String> {
+        let mut key = String::new();
+        while let Some(character) = self.peek().filter(|&c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+            key.push(character);
+            self.next();
+        }
+        if key.is_empty() {
+            return Err("expected a key".to_string());
+        }
+        self.skip_spaces();
+        self.expect('=')?;
+        self.skip_spaces();
+        let value = self.value()?;
+        self.skip_spaces();
+        self.skip_comment();
+        match self.peek() {
+            None | Some('\n') | Some('\r') => Ok((key, value)),
+            Some(_) => Err("expected the end of the line".to_string()),
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        match self.peek() {
+            Some('"') | Some('\'') => self.string().map(Value::Text),
+            Some('[') => self.list().map(Value::List),
+            Some('t') | Some('f') => {
+                let mut word = String::new();
+                while let Some(character) = self.peek().filter(|c| c.is_ascii_alphabetic()) {
+                    word.push(character);
+                    self.next();
+                }
+                match &word[..] {
+                    "true" => Ok(Value::Bool(true)),
+                    "false" => Ok(Value::Bool(false)),
+                    _ => Err(format!("unexpected {}", word)),
+                }
+            },
+            _ => Err("expected a string, a list or a boolean".to_string()),
+        }
+    }
+
+    // "basic" strings have escapes, 'literal' ones don't
+    fn string(&mut self) -> Result<String, String> {
+        let quote = self.next().unwrap();
+        let mut string = String::new();
+        loop {
+            match self.next() {
+                None | Some('\n') => return Err("unfinished string".to_string()),
+                Some(character) if character == quote => return Ok(string),
+                Some('\\') if quote == '"' => string.push(self.escape()?),
+                Some(character) => string.push(character),
+            }
+        }
+    }
+
+    fn escape(&mut self) -> Result<char, String> {
+        match self.next() {
+            Some('n') => Ok('\n'),
+            Some('t') => Ok('\t'),
+            Some('r') => Ok('\r'),
+            Some('\\') => Ok('\\'),
+            Some('"') => Ok('"'),
+            Some('u') => {
+                let digits: String = (0..4).filter_map(|_| self.next()).collect();
+                u32::from_str_radix(&digits, 16).ok()
+                    .and_then(std::char::from_u32)
+                    .ok_or_else(|| format!("bad escape \\u{}", digits))
+            },
+            _ => Err("bad escape".to_string()),
+        }
+    }
+
+    // lists of strings, which can go over several lines
+    fn list(&mut self) -> Result<Vec<String>, String> {
+        self.expect('[')?;
+        let mut list = Vec::new();
+        loop {
+            self.skip_blank();
+            match self.peek() {
+                Some(']') => {
+                    self.next();
+                    return Ok(list);
+                },
+                Some('"') | Some('\'') => list.push(self.string()?),
+                _ => return Err("expected a string or ]".to_string()),
+            }
+            self.skip_blank();
+            match self.peek() {
+                Some(',') => { self.next(); },
+                Some(']') => {},
+                _ => return Err("expected , or ]".to_string()),
+            }
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_parse_values() {
+        let text = "# a comment\n\na = \"x\\\"y\\u00e9\"  # another\n\
+                    b = [\n  'c:\\d', # literal\n  \"e\",\n]\nc = true\n";
+        let pairs = parse(text).unwrap();
+        let lines: Vec<usize> = pairs.iter().map(|pair| pair.0).collect();
+        assert_eq!(lines, vec![3, 4, 8]);
+        match pairs[0].2 {
+            Value::Text(ref text) => assert_eq!(text, "x\"yé"),
+            _ => panic!("a is a string"),
+        }
+        match pairs[1].2 {
+            Value::List(ref list) => assert_eq!(list, &vec!["c:\\d".to_string(), "e".to_string()]),
+            _ => panic!("b is a list"),
+        }
+        match pairs[2].2 {
+            Value::Bool(value) => assert!(value),
+            _ => panic!("c is a boolean"),
+        }
+    }
+}
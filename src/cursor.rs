@@ -0,0 +1,80 @@
+//! Positions in a buffer.
+//!
+//! A cursor is a column and a line, both counted in chars from zero. It can
+//! be past the end of its line or below the last one; typing there fills the
+//! gap with spaces and empty lines.
+
+/// Where the cursor is, `x` chars into line `y`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Cursor {
+    pub x: usize,
+    pub y: usize,
+}
+
+impl Cursor {
+    pub fn new(x: usize, y: usize) -> Cursor {
+        Cursor {x, y}
+    }
+}
+
+/// The text between the mark and the cursor, whichever comes first. The end
+/// is not included.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Region {
+    pub start: Cursor,
+    pub end: Cursor,
+}
+
+impl Region {
+    pub fn new(mark: Cursor, cursor: Cursor) -> Region {
+        if (mark.y, mark.x) <= (cursor.y, cursor.x) {
+            Region {start: mark, end: cursor}
+        } else {
+            Region {start: cursor, end: mark}
+        }
+    }
+
+    /// Selected columns of a line as a half open range. A line selected up
+    /// to its end gets one more column so the newline shows up as selected
+    /// too.
+    pub fn columns(&self, line_number: usize, line_length: usize) -> Option<(usize, usize)> {
+        if line_number < self.start.y || line_number > self.end.y {
+            return None;
+        }
+        let start = if line_number == self.start.y { self.start.x } else { 0 };
+        let end = if line_number == self.end.y { self.end.x } else { line_length + 1 };
+        if start < end { Some((start, end)) } else { None }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_region_is_ordered() {
+        let region = Region::new(Cursor::new(3, 2), Cursor::new(5, 0));
+        assert_eq!(region.start, Cursor::new(5, 0));
+        assert_eq!(region.end, Cursor::new(3, 2));
+
+        let region = Region::new(Cursor::new(1, 1), Cursor::new(4, 1));
+        assert_eq!(region.start, Cursor::new(1, 1));
+        assert_eq!(region.end, Cursor::new(4, 1));
+    }
+
+    #[test]
+    fn test_region_columns() {
+        let region = Region::new(Cursor::new(2, 1), Cursor::new(3, 3));
+        assert_eq!(region.columns(0, 10), None);
+        assert_eq!(region.columns(1, 10), Some((2, 11)));
+        assert_eq!(region.columns(2, 4), Some((0, 5)));
+        assert_eq!(region.columns(3, 10), Some((0, 3)));
+        assert_eq!(region.columns(4, 10), None);
+
+        // nothing selected
+        let region = Region::new(Cursor::new(2, 1), Cursor::new(2, 1));
+        assert_eq!(region.columns(1, 10), None);
+    }
+}
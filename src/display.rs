@@ -0,0 +1,489 @@
+//! Drawing a buffer.
+//!
+//! The Display keeps track of which part of the buffer is on screen, scrolling
+//! to follow the cursor, and draws it through a Backend with the colours of a
+//! Theme: syntax highlighting, search matches and the selection on top, line
+//! numbers on the left and a status bar or a prompt in the bottom row. It can
+//! be given just part of the screen, so several of them share a Backend when
+//! it's split.
+
+use std::env;
+use std::iter;
+use std::rc::Rc;
+
+use backend::Backend;
+use buffer::{Buffer, BufferChanges};
+use cursor::{Cursor, Region};
+use gutter::{Gutter, Numbering};
+use highlight;
+use layout::Rect;
+use search;
+use theme::{Class, Face, Theme};
+use wrap::{self, wrap_line};
+
+// a char on screen and how it's drawn
+#[derive(Clone, Copy)]
+struct Styled {
+    character: char,
+    face: Face,
+}
+
+// Draws `text` into `cells` starting at `column`, adding blank cells in front
+// if the line isn't that long yet. `blank` is how those look.
+fn paint(cells: &mut Vec<Styled>, column: usize, face: Face, blank: Face, text: &str) {
+    for (i, character) in text.chars().enumerate() {
+        let cell = Styled {character, face};
+        let x = column + i;
+        while cells.len() < x {
+            cells.push(Styled {character: ' ', face: blank});
+        }
+        if x < cells.len() {
+            cells[x] = cell;
+        } else {
+            cells.push(cell);
+        }
+    }
+}
+
+/// Draws a buffer on a Backend: the lines that fit, a gutter with line
+/// numbers, a status bar at the bottom and the cursor.
+pub struct Display {
+    pub(crate) backend: Rc<dyn Backend>,
+    // the part of the screen that's ours
+    left: usize,
+    top: usize,
+    width: usize,
+    height: usize,
+    pub(crate) vertical_offset: usize,
+    // rows of the top line scrolled past, when wrapping it takes more rows
+    // than there are on screen
+    skipped_rows: usize,
+    // first column of the lines that's on screen
+    pub(crate) horizontal_offset: usize,
+    // columns kept between the cursor and the sides of the screen
+    side_margin: usize,
+    // long lines go on over several rows instead of scrolling sideways
+    pub(crate) wrap: bool,
+    pub(crate) selection: Option<Region>,
+    // text shown in the bottom row while asking for input, instead of the
+    // status bar
+    pub(crate) prompt: Option<String>,
+    // shown in the status bar until the next key
+    pub(crate) message: Option<String>,
+    // matches of this are highlighted
+    pub(crate) search_query: Option<String>,
+    // line numbers, the text starts right after them
+    pub(crate) gutter: Gutter,
+    pub(crate) theme: Theme,
+}
+
+impl Display {
+    /// Takes up the whole backend, the buffer on top and the status bar in the
+    /// bottom row.
+    pub fn new(backend: Box<dyn Backend>, theme: Theme) -> Display {
+        let width = backend.width();
+        let height = backend.height();
+        Display {
+            backend: Rc::from(backend),
+            left: 0,
+            top: 0,
+            width,
+            height,
+            vertical_offset: 0,
+            skipped_rows: 0,
+            horizontal_offset: 0,
+            side_margin: side_margin_from_env(),
+            wrap: false,
+            selection: None,
+            prompt: None,
+            message: None,
+            search_query: None,
+            gutter: Gutter::new(Numbering::from_env()),
+            theme,
+        }
+    }
+
+    // Another display on the same backend, looking at things the same way,
+    // for another view. It needs to be placed.
+    pub(crate) fn sibling(&self) -> Display {
+        Display {
+            backend: self.backend.clone(),
+            selection: None,
+            prompt: None,
+            message: None,
+            search_query: None,
+            gutter: Gutter::new(self.gutter.numbering),
+            theme: self.theme.clone(),
+            ..*self
+        }
+    }
+
+    // moves to `area` of the screen, it gets drawn again from scratch
+    pub(crate) fn place(&mut self, area: Rect) {
+        self.left = area.x;
+        self.top = area.y;
+        self.width = area.width;
+        self.height = area.height;
+    }
+
+    // rows available for the buffer, the last one is for the status bar
+    pub(crate) fn text_height(&self) -> usize {
+        self.height.saturating_sub(1)
+    }
+
+    // columns available for the text, right of the gutter
+    fn text_width(&self) -> usize {
+        self.width.saturating_sub(self.gutter.width())
+    }
+
+    /// columns lines are wrapped at, if they are
+    pub fn wrap_width(&self) -> Option<usize> {
+        if self.wrap { Some(self.text_width()) } else { None }
+    }
+
+    // Screen rows taken by the lines from `top` down to the cursor's row, the
+    // cursor's row included. Only used when wrapping.
+    fn rows_to(&self, buffer: &Buffer, top: usize, cursor: &Cursor) -> usize {
+        let width = self.text_width();
+        let above: usize = (top..cursor.y)
+            .map(|line_number| wrap_line(&buffer.get_line(line_number), width).len())
+            .sum();
+        let line = buffer.get_line(cursor.y);
+        let x = cursor.x.min(line.chars().count());
+        above + wrap::visual_position(&wrap_line(&line, width), x).0 + 1
+    }
+
+    /// Shows `message` in the status bar until the next key.
+    pub fn show_message(&mut self, message: String) {
+        self.message = Some(message);
+    }
+
+    /// Keep the cursor on screen, scrolling as little as possible. Returns
+    /// whether we had to scroll.
+    pub fn scroll_to(&mut self, buffer: &Buffer, cursor: &Cursor) -> bool {
+        let (vertical_offset, skipped_rows, horizontal_offset) = if self.wrap {
+            // the top of the screen is the start of a line, and every line
+            // takes at least a row
+            let height = self.text_height();
+            let mut top = self.vertical_offset.min(cursor.y).max((cursor.y + 1).saturating_sub(height));
+            while top < cursor.y && self.rows_to(buffer, top, cursor) > height {
+                top += 1;
+            }
+            // unless the cursor's line doesn't fit, then we go through it a
+            // row at a time
+            let skipped_rows = if top == cursor.y {
+                let row = self.rows_to(buffer, top, cursor) - 1;
+                let rows = wrap_line(&buffer.get_line(top), self.text_width()).len();
+                let skipped_rows = if top == self.vertical_offset { self.skipped_rows } else { 0 };
+                skipped_rows.min(row).min(rows.saturating_sub(height)).max((row + 1).saturating_sub(height))
+            } else {
+                0
+            };
+            (top, skipped_rows, 0)
+        } else {
+            // sideways we keep some room around the cursor so the
+            // continuation markers never cover it
+            (scroll_offset(self.vertical_offset, cursor.y, self.text_height(), 0), 0,
+             scroll_offset(self.horizontal_offset, cursor.x, self.text_width(), self.side_margin))
+        };
+
+        let offsets = (vertical_offset, skipped_rows, horizontal_offset);
+        let scrolled = offsets != (self.vertical_offset, self.skipped_rows, self.horizontal_offset);
+        self.vertical_offset = vertical_offset;
+        self.skipped_rows = skipped_rows;
+        self.horizontal_offset = horizontal_offset;
+        scrolled
+    }
+
+    /// Like scroll_to, but a cursor that's off screen ends up in the middle.
+    pub fn center_on(&mut self, cursor: &Cursor) {
+        let height = self.text_height().max(1);
+        if cursor.y < self.vertical_offset || cursor.y >= self.vertical_offset + height {
+            self.vertical_offset = cursor.y.saturating_sub(height / 2);
+            self.skipped_rows = 0;
+        }
+    }
+
+    fn clear_line(&self, line_number: usize) {
+        let blank_line: String = (0..self.width).map(|_| " ").collect();
+        self.print(0, line_number, Class::Text, &blank_line);
+    }
+
+    // draws what goes in front of a row, the line number or blanks
+    fn render_gutter(&self, row: usize, label: &str) {
+        self.print(0, row, Class::Gutter, label);
+    }
+
+    // Puts cells `start..end` of a line on screen row `row`, right of the
+    // gutter.
+    fn draw_cells(&self, cells: &[Styled], row: usize, start: usize, end: usize) {
+        let left = self.gutter.width();
+        let end = end.min(cells.len()).min(start + self.text_width());
+        for (i, cell) in cells.iter().enumerate().take(end).skip(start) {
+            self.backend.print_char(self.left + left + i - start, self.top + row, cell.face, cell.character);
+        }
+    }
+
+    // marks the sides of a line that go on past the screen
+    fn render_continuation(&self, line: &str, row: usize) {
+        let width = self.text_width();
+        if width < 2 {
+            return;
+        }
+        let line_length = line.chars().count();
+        if self.horizontal_offset > 0 && line_length > 0 {
+            self.print(self.gutter.width(), row, Class::Continuation, "<");
+        }
+        if line_length > self.horizontal_offset + width {
+            self.print(self.gutter.width() + width - 1, row, Class::Continuation, ">");
+        }
+    }
+
+    /// Puts the terminal cursor where the buffer cursor is on screen.
+    pub fn render_cursor(&self, buffer: &Buffer, cursor: &Cursor) {
+        let (column, row) = if self.wrap {
+            let line = buffer.get_line(cursor.y);
+            let x = cursor.x.min(line.chars().count());
+            let column = wrap::visual_position(&wrap_line(&line, self.text_width()), x).1;
+            (column, self.rows_to(buffer, self.vertical_offset, cursor) - 1 - self.skipped_rows)
+        } else {
+            (cursor.x.saturating_sub(self.horizontal_offset), cursor.y - self.vertical_offset)
+        };
+        self.backend.set_cursor(Some((self.left + self.gutter.width() + column, self.top + row)));
+    }
+
+    // How line `line_number` looks, before deciding what part of it goes
+    // where on screen.
+    fn line_cells(&self, buffer: &Buffer, line_number: usize) -> Vec<Styled> {
+        let line = buffer.get_line(line_number);
+        let text = self.theme.face(Class::Text);
+        let mut cells: Vec<Styled> = line.chars().map(|character| Styled {character, face: text}).collect();
+        for span in highlight::lex_line(&buffer.language, &line, buffer.line_state(line_number)).0 {
+            let face = self.theme.token(span.token);
+            // a span past the end of the line would be the lexer's mistake,
+            // not a reason to crash
+            for cell in cells.iter_mut().take(span.end).skip(span.start) {
+                cell.face = face;
+            }
+        }
+        self.render_matches(&mut cells, &line);
+        self.render_selection(&mut cells, &line, line_number);
+        cells
+    }
+
+    fn render_line(&self, buffer: &Buffer, row: usize) {
+        self.clear_line(row);
+        let line_number = row + self.vertical_offset;
+        self.render_gutter(row, &self.gutter.label(line_number));
+        let cells = self.line_cells(buffer, line_number);
+        self.draw_cells(&cells, row, self.horizontal_offset, self.horizontal_offset + self.text_width());
+        self.render_continuation(&buffer.get_line(line_number), row);
+    }
+
+    // Draws a line over as many rows as it needs starting at `row`, as long
+    // as they're on screen, leaving out its first `skip` rows. Returns how
+    // many rows it took.
+    fn render_wrapped_line(&self, buffer: &Buffer, line_number: usize, row: usize, skip: usize) -> usize {
+        let cells = self.line_cells(buffer, line_number);
+        let starts = wrap_line(&buffer.get_line(line_number), self.text_width());
+        let blank = " ".repeat(self.gutter.width());
+        for (i, &start) in starts.iter().enumerate().skip(skip) {
+            let screen_row = row + i - skip;
+            if screen_row >= self.text_height() {
+                break;
+            }
+            self.clear_line(screen_row);
+            if i == 0 {
+                self.render_gutter(screen_row, &self.gutter.label(line_number));
+            } else {
+                self.render_gutter(screen_row, &blank);
+            }
+            let end = starts.get(i + 1).cloned().unwrap_or(start + self.text_width());
+            self.draw_cells(&cells, screen_row, start, end);
+        }
+        starts.len().saturating_sub(skip)
+    }
+
+    fn render_matches(&self, cells: &mut Vec<Styled>, line: &str) {
+        let query = match self.search_query {
+            Some(ref query) => query,
+            None => return,
+        };
+        for (start, end) in search::find_in_line(line, query) {
+            for (x, character) in line.chars().enumerate().take(end).skip(start) {
+                paint(cells, x, self.theme.face(Class::Match), self.theme.face(Class::Text),
+                      &character.to_string());
+            }
+        }
+    }
+
+    // paint the selected part of a line over whatever line_cells drew
+    fn render_selection(&self, cells: &mut Vec<Styled>, line: &str, line_number: usize) {
+        let region = match self.selection {
+            Some(region) => region,
+            None => return,
+        };
+        let line_length = line.chars().count();
+        if let Some((start, end)) = region.columns(line_number, line_length) {
+            let characters = line.chars().chain(iter::repeat(' '));
+            for (x, character) in characters.enumerate().take(end).skip(start) {
+                paint(cells, x, self.theme.face(Class::Selection), self.theme.face(Class::Text),
+                      &character.to_string());
+            }
+        }
+    }
+
+    /// Draws what changed again. The buffer needs to be highlighted down to
+    /// the bottom of the screen, see Buffer::highlight_to.
+    pub fn render_buffer_changes(&self, buffer: &Buffer, changes: BufferChanges) {
+        match changes {
+            BufferChanges::Buffer          => self.render_buffer(buffer),
+            // a changed line may now take a different number of rows
+            BufferChanges::Lines(_) if self.wrap => self.render_buffer(buffer),
+            BufferChanges::Lines(lines)    => {
+                let on_screen = self.vertical_offset..self.vertical_offset + self.text_height();
+                for line_number in lines.into_iter().filter(|line| on_screen.contains(line)) {
+                    self.render_line(buffer, line_number - self.vertical_offset);
+                }
+            }
+            BufferChanges::Char((_, line_number)) => {
+                self.render_buffer_changes(buffer, BufferChanges::Lines(vec![line_number]));
+            },
+            BufferChanges::None            => {},
+        };
+    }
+
+    fn render_buffer(&self, buffer: &Buffer) {
+        for row in 0..self.height {
+            self.clear_line(row);
+        }
+        if self.wrap {
+            let mut row = self.render_wrapped_line(buffer, self.vertical_offset, 0, self.skipped_rows);
+            let mut line_number = self.vertical_offset + 1;
+            while row < self.text_height() {
+                row += self.render_wrapped_line(buffer, line_number, row, 0);
+                line_number += 1;
+            }
+            return;
+        }
+        for i in self.vertical_offset..(self.vertical_offset + self.text_height()) {
+            self.render_line(buffer, i - self.vertical_offset);
+        }
+    }
+
+    /// Draws `lines` over the buffer, a row each, with the `selected` one
+    /// highlighted and on screen.
+    pub fn render_list(&self, lines: &[&str], selected: usize) {
+        let height = self.text_height();
+        let top = scroll_offset(0, selected, height, 0);
+        for row in 0..height {
+            self.clear_line(row);
+            if let Some(line) = lines.get(top + row) {
+                let class = if top + row == selected { Class::Selection } else { Class::Text };
+                let text: String = format!(" {}", line).chars().chain(iter::repeat(' ')).take(self.width).collect();
+                self.print(0, row, class, &text);
+            }
+        }
+    }
+
+    /// The status bar, with the file name, a message if there's one and where
+    /// the cursor is.
+    pub fn render_status(&self, filename: &str, buffer: &Buffer, cursor: &Cursor) {
+        if self.prompt.is_some() || self.height == 0 {
+            return;
+        }
+        let modified = if buffer.modified { " [+]" } else { "" };
+        let left = match self.message {
+            Some(ref message) => format!(" {}{}  {}", filename, modified, message),
+            None => format!(" {}{}", filename, modified),
+        };
+        let right = format!("{}  {}  Ln {}, Col {}  {} lines ", buffer.language.name, buffer.line_ending.name(),
+                            cursor.y + 1, cursor.x + 1, buffer.count_lines());
+        self.print(0, self.height - 1, Class::Status, &status_line(&left, &right, self.width));
+    }
+
+    /// draws the prompt, if any, and leaves the cursor at its end
+    pub fn render_prompt(&self) {
+        if let Some(ref prompt) = self.prompt {
+            let row = self.height.saturating_sub(1);
+            self.clear_line(row);
+            self.print(0, row, Class::Prompt, prompt);
+            self.backend.set_cursor(Some((self.left + prompt.chars().count().min(self.width), self.top + row)));
+        }
+    }
+
+    fn print(&self, x: usize, y: usize, class: Class, text: &str) {
+        let text: String = text.chars().take(self.width.saturating_sub(x)).collect();
+        self.backend.print(self.left + x, self.top + y, self.theme.face(class), &text);
+    }
+
+    /// Shows everything drawn since the last flush.
+    pub fn flush(&self) {
+        self.backend.present();
+    }
+}
+
+// Where a view `size` long should start so `position` is inside it, at least
+// `margin` away from its ends when possible, moving it as little as we can.
+fn scroll_offset(offset: usize, position: usize, size: usize, margin: usize) -> usize {
+    let size = size.max(1);
+    let margin = margin.min((size - 1) / 2);
+    if position + margin >= offset + size {
+        position + margin + 1 - size
+    } else if position < offset + margin {
+        position.saturating_sub(margin)
+    } else {
+        offset
+    }
+}
+
+// EDITOR2_SIDE_MARGIN, how close the cursor gets to the sides before the view
+// scrolls sideways
+fn side_margin_from_env() -> usize {
+    env::var("EDITOR2_SIDE_MARGIN").ok()
+        .and_then(|margin| margin.parse().ok())
+        .unwrap_or(4)
+}
+
+// `left` and `right` pushed to both ends of a line `width` columns wide. If
+// they don't fit the right side goes first, then the left is cut short.
+fn status_line(left: &str, right: &str, width: usize) -> String {
+    let right_length = right.chars().count();
+    let right = if right_length + 1 + left.chars().count() > width { "" } else { right };
+    let mut line: String = left.chars().take(width).collect();
+    let padding = width - line.chars().count() - right.chars().count();
+    line.extend((0..padding).map(|_| ' '));
+    line.push_str(right);
+    line
+}
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_status_line() {
+        assert_eq!(status_line(" file", "Ln 1 ", 16), " file      Ln 1 ");
+        // no room for the right side
+        assert_eq!(status_line(" file", "Ln 1 ", 8), " file   ");
+        assert_eq!(status_line(" a long name", "Ln 1 ", 6), " a lon");
+    }
+
+    #[test]
+    fn test_scroll_offset() {
+        // already in view
+        assert_eq!(scroll_offset(0, 5, 10, 0), 0);
+        assert_eq!(scroll_offset(10, 15, 10, 2), 10);
+        // past either end, just enough to bring it in
+        assert_eq!(scroll_offset(0, 10, 10, 0), 1);
+        assert_eq!(scroll_offset(5, 3, 10, 0), 3);
+        // keeping the margin
+        assert_eq!(scroll_offset(0, 8, 10, 2), 1);
+        assert_eq!(scroll_offset(10, 11, 10, 2), 9);
+        assert_eq!(scroll_offset(10, 1, 10, 4), 0);
+        // the margin can't take more than half the view
+        assert_eq!(scroll_offset(0, 3, 4, 5), 1);
+    }
+}
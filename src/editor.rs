@@ -0,0 +1,1057 @@
+//! The editor: the open buffers, the views on screen showing them, and what
+//! the keys do to them.
+//!
+//! Besides the editing commands, keys search, replace, select, copy and
+//! paste, save and quit, open, switch between and close buffers, and split
+//! the screen into views. Unsaved changes are kept in a swap file in case we
+//! don't get to quit cleanly.
+
+use std::path::PathBuf;
+
+use backend::Key;
+use buffer::{Buffer, BufferChanges, LineEnding};
+use clipboard::Clipboard;
+use commands::{apply_command, get_next_cursor};
+use cursor::{Cursor, Region};
+use display::Display;
+use error::Error;
+use input::{Input, InputReader};
+use io;
+use language::Languages;
+use layout::{self, Layout, Orientation, Rect};
+use picker::{Choice, Picker};
+use replace::{Replace, Step};
+use search::{Direction, Outcome, Search};
+use swap::Swap;
+use theme::Class;
+
+// a file open in the editor
+struct OpenBuffer {
+    path: PathBuf,
+    shown_filename: String,
+    buffer: Buffer,
+    // where the last view to show it left it
+    cursor: Cursor,
+    vertical_offset: usize,
+    horizontal_offset: usize,
+    swap: Swap,
+    // the text of a swap file left behind, until we're told whether to use it
+    recovery: Option<String>,
+}
+
+// A part of the screen showing one of the buffers. Several views can show
+// the same one, each with its own cursor and scrolled its own way.
+struct View {
+    buffer: usize,
+    cursor: Cursor,
+    display: Display,
+}
+
+// what we're asking whether to save first
+#[derive(Clone, Copy)]
+enum Closing {
+    Buffer,
+    Editor,
+}
+
+// what a key does to the buffers and the views, done once it's been handled
+enum Action {
+    Show(usize),
+    Open(PathBuf),
+    Pick,
+    Close,
+    Split(Orientation),
+    // to the view next to this one in the direction of an arrow key
+    Focus(Key),
+    FocusNext,
+    Resize(isize),
+    CloseView,
+}
+
+/// The files being edited, the views on screen showing them, and what the
+/// keys do to them.
+pub struct Editor {
+    buffers: Vec<OpenBuffer>,
+    views: Vec<View>,
+    // how the screen is shared between the views
+    layout: Layout,
+    // the view the keys go to
+    focused: usize,
+    input: InputReader,
+    clipboard: Clipboard,
+    languages: Languages,
+    // where the selection started, if there's one
+    mark: Option<Cursor>,
+    search: Option<Search>,
+    replace: Option<Replace>,
+    // what's been typed of the name of a file to open
+    opening: Option<String>,
+    picker: Option<Picker>,
+    closing: Option<Closing>,
+    // after Ctrl-K, the next key splits, moves between or closes views
+    arranging: bool,
+}
+
+impl Editor {
+    /// An editor with no files open yet, see open, that starts out with one
+    /// view taking all of `display`. `languages` pick how files are
+    /// highlighted.
+    pub fn new(display: Display, clipboard: Clipboard, languages: Languages) -> Editor {
+        Editor {
+            buffers: Vec::new(),
+            views: vec![View {buffer: 0, cursor: Cursor::new(0, 0), display}],
+            layout: Layout::View(0),
+            focused: 0,
+            input: InputReader::new(),
+            clipboard,
+            languages,
+            mark: None,
+            search: None,
+            replace: None,
+            opening: None,
+            picker: None,
+            closing: None,
+            arranging: false,
+        }
+    }
+
+    /// Opens `path` in a new buffer, or finds the one it's already open in,
+    /// and shows it in the focused view. A swap file left behind for it is
+    /// offered back.
+    pub fn open(&mut self, path: PathBuf) -> Result<(), Error> {
+        let index = self.load(path)?;
+        self.show(index);
+        Ok(())
+    }
+
+    // adds a buffer for `path` unless there's one, returns where it is
+    fn load(&mut self, path: PathBuf) -> Result<usize, Error> {
+        if let Some(index) = self.buffers.iter().position(|open| open.path == path) {
+            return Ok(index);
+        }
+        let mut buffer = io::open(&path)?;
+        buffer.set_language(self.languages.detect(&path.to_string_lossy(), &buffer.get_line(0)));
+        let swap = Swap::new(&path);
+        let recovery = swap.recoverable(&path);
+        self.buffers.push(OpenBuffer {
+            shown_filename: path.to_string_lossy().into_owned(),
+            path,
+            buffer,
+            cursor: Cursor::new(0, 0),
+            vertical_offset: 0,
+            horizontal_offset: 0,
+            swap,
+            recovery,
+        });
+        Ok(self.buffers.len() - 1)
+    }
+
+    /// Puts buffer `index`, counting in the order they were opened, in the
+    /// focused view.
+    pub fn show(&mut self, index: usize) {
+        self.switch_to(index);
+        self.render(BufferChanges::Buffer);
+    }
+
+    fn switch_to(&mut self, index: usize) {
+        let focused = self.focused;
+        self.leave(focused);
+        self.enter(focused, index);
+    }
+
+    // remembers where view `view` was in its buffer
+    fn leave(&mut self, view: usize) {
+        let view = &self.views[view];
+        if let Some(open) = self.buffers.get_mut(view.buffer) {
+            open.cursor = view.cursor;
+            open.vertical_offset = view.display.vertical_offset;
+            open.horizontal_offset = view.display.horizontal_offset;
+        }
+    }
+
+    // shows buffer `index` in view `view`, back where it was left
+    fn enter(&mut self, view: usize, index: usize) {
+        let open = &self.buffers[index];
+        let view = &mut self.views[view];
+        view.buffer = index;
+        view.cursor = open.cursor;
+        view.display.vertical_offset = open.vertical_offset;
+        view.display.horizontal_offset = open.horizontal_offset;
+        // the selection was in the buffer we left
+        self.mark = None;
+    }
+
+    // Throws away the buffer in the focused view. The views that showed it
+    // move on to the next one. Returns false if it was the last one.
+    fn close(&mut self) -> bool {
+        let index = self.views[self.focused].buffer;
+        let mut closed = self.buffers.remove(index);
+        closed.swap.remove(closed.buffer.revision);
+        if self.buffers.is_empty() {
+            return false;
+        }
+        for view in 0..self.views.len() {
+            if self.views[view].buffer == index {
+                let next = index.min(self.buffers.len() - 1);
+                self.enter(view, next);
+            } else if self.views[view].buffer > index {
+                self.views[view].buffer -= 1;
+            }
+        }
+        true
+    }
+
+    // all of the screen, for the views to share
+    fn screen(&self) -> Rect {
+        let backend = &self.views[self.focused].display.backend;
+        Rect::new(0, 0, backend.width(), backend.height())
+    }
+
+    // Where each view goes on screen, and the separators between them.
+    fn arrange(&self) -> (Vec<(usize, Rect)>, Vec<Rect>) {
+        self.layout.arrange(self.screen())
+    }
+
+    // Splits the focused view in two, the new half shows the same and gets
+    // the keys.
+    fn split(&mut self, orientation: Orientation) -> Result<(), String> {
+        let area = self.arrange().0.into_iter()
+            .find(|&(view, _)| view == self.focused)
+            .map(|(_, area)| area).unwrap();
+        let new = self.views.len();
+        if !self.layout.split(self.focused, new, orientation, area) {
+            return Err("No room to split".to_string());
+        }
+        let view = &self.views[self.focused];
+        let split = View {buffer: view.buffer, cursor: view.cursor, display: view.display.sibling()};
+        self.views.push(split);
+        self.focus(new);
+        Ok(())
+    }
+
+    fn focus(&mut self, view: usize) {
+        self.focused = view;
+        self.mark = None;
+    }
+
+    /// Handles keys until the user quits. There has to be a file open.
+    pub fn run(&mut self) {
+        while self.step() { }
+        // quitting cleanly, the files have all we want to keep
+        for open in &mut self.buffers {
+            open.swap.remove(open.buffer.revision);
+        }
+    }
+
+    /// Waits for the next input and handles it. Returns false when it's time
+    /// to quit.
+    pub fn step(&mut self) -> bool {
+        let timeout = self.buffers.iter().filter_map(|open| open.swap.timeout()).min();
+        let event = self.input.poll(self.views[self.focused].display.backend.as_ref(), timeout);
+        self.handle(event)
+    }
+
+    fn handle(&mut self, event: Option<Input>) -> bool {
+        let mut buffer_changes = BufferChanges::None;
+        let had_selection = self.mark.is_some();
+        let mut action = None;
+        let count = self.buffers.len();
+        let first_modified = self.buffers.iter().position(|open| open.buffer.modified);
+        if event == Some(Input::Resize) {
+            // the views get their share of the new size, scrolling to keep
+            // their cursors on screen
+            self.redraw();
+            return true;
+        }
+        let view = &mut self.views[self.focused];
+        let current = view.buffer;
+        let open = &mut self.buffers[current];
+        if event.is_some() {
+            view.display.message = None;
+        }
+
+        if let Some(text) = open.recovery.take() {
+            match event {
+                Some(Input::Key(Key::Char('y'))) | Some(Input::Key(Key::Char('Y'))) => {
+                    let language = open.buffer.language.clone();
+                    open.buffer = Buffer::from_string(&text);
+                    open.buffer.set_language(language);
+                    // it's not what's in the file
+                    open.buffer.mark_modified();
+                    view.display.message = Some(format!("Recovered {} lines", open.buffer.count_lines()));
+                },
+                Some(Input::Key(Key::Char('n'))) | Some(Input::Key(Key::Char('N'))) => {
+                    open.swap.remove(open.buffer.revision);
+                },
+                _ => open.recovery = Some(text),
+            }
+            buffer_changes = BufferChanges::Buffer;
+        } else if let Some(mut active) = self.search.take() {
+            // keys go to the self.search prompt until it's done
+            if let Some(Input::Key(key)) = event {
+                match active.handle_key(key, &open.buffer, &view.cursor) {
+                    Outcome::Searching(found) => {
+                        view.cursor = found;
+                        view.display.center_on(&view.cursor);
+                        self.search = Some(active);
+                    },
+                    Outcome::Accepted => { },
+                    Outcome::Cancelled => {
+                        view.cursor = active.origin;
+                        view.display.vertical_offset = active.origin_offset;
+                    },
+                }
+            } else {
+                self.search = Some(active);
+            }
+            buffer_changes = BufferChanges::Buffer;
+        } else if let Some(mut active) = self.replace.take() {
+            if let Some(Input::Key(key)) = event {
+                match active.handle_key(key, &mut open.buffer, &view.cursor) {
+                    Step::Asking(position) => {
+                        view.cursor = position;
+                        view.display.center_on(&view.cursor);
+                        self.replace = Some(active);
+                    },
+                    Step::Done(position) => {
+                        view.cursor = position;
+                        view.display.message = Some(format!("Replaced {} matches", active.replaced()));
+                    },
+                    Step::Cancelled(position) => view.cursor = position,
+                }
+            } else {
+                self.replace = Some(active);
+            }
+            buffer_changes = BufferChanges::Buffer;
+        } else if let Some(mut typed) = self.opening.take() {
+            if let Some(Input::Key(key)) = event {
+                match key {
+                    Key::Enter if !typed.is_empty() => action = Some(Action::Open(PathBuf::from(typed))),
+                    Key::Esc | Key::Ctrl('g') => { },
+                    Key::Backspace => {
+                        typed.pop();
+                        self.opening = Some(typed);
+                    },
+                    Key::Char(character) => {
+                        typed.push(character);
+                        self.opening = Some(typed);
+                    },
+                    _ => self.opening = Some(typed),
+                }
+            } else {
+                self.opening = Some(typed);
+            }
+        } else if let Some(mut picker) = self.picker.take() {
+            if let Some(Input::Key(key)) = event {
+                match picker.handle_key(key) {
+                    Choice::Picking => self.picker = Some(picker),
+                    Choice::Picked(index) => action = Some(Action::Show(index)),
+                    Choice::Cancelled => { },
+                }
+            } else {
+                self.picker = Some(picker);
+            }
+            buffer_changes = BufferChanges::Buffer;
+        } else if let Some(closing) = self.closing.take() {
+            if let Some(Input::Key(key)) = event {
+                match (key, closing) {
+                    (Key::Char('y'), Closing::Buffer) | (Key::Char('Y'), Closing::Buffer) => {
+                        match io::save_buffer(&open.path, &open.buffer) {
+                            Ok(()) => action = Some(Action::Close),
+                            Err(error) => view.display.message = Some(format!("Couldn't save: {}", error)),
+                        }
+                    },
+                    (Key::Char('y'), Closing::Editor) | (Key::Char('Y'), Closing::Editor) => {
+                        match save_all(&mut self.buffers) {
+                            Ok(()) => return false,
+                            Err(message) => view.display.message = Some(message),
+                        }
+                    },
+                    (Key::Char('n'), Closing::Buffer) | (Key::Char('N'), Closing::Buffer) => {
+                        action = Some(Action::Close);
+                    },
+                    (Key::Char('n'), Closing::Editor) | (Key::Char('N'), Closing::Editor) => return false,
+                    (Key::Char('c'), _) | (Key::Char('C'), _) | (Key::Esc, _) | (Key::Ctrl('g'), _) => { },
+                    _ => self.closing = Some(closing),
+                }
+            } else {
+                self.closing = Some(closing);
+            }
+            buffer_changes = BufferChanges::Buffer;
+        } else if self.arranging {
+            if let Some(Input::Key(key)) = event {
+                self.arranging = false;
+                match key {
+                    Key::Char('s')       => action = Some(Action::Split(Orientation::Stacked)),
+                    Key::Char('v')       => action = Some(Action::Split(Orientation::SideBySide)),
+                    Key::Up | Key::Down | Key::Left | Key::Right => action = Some(Action::Focus(key)),
+                    Key::Char('o')       => action = Some(Action::FocusNext),
+                    Key::Char('+')       => action = Some(Action::Resize(1)),
+                    Key::Char('-')       => action = Some(Action::Resize(-1)),
+                    Key::Char('c')       => action = Some(Action::CloseView),
+                    _                    => { },
+                }
+            }
+        } else {
+            match event {
+                Some(Input::Shift(direction)) => {
+                    // the self.mark stays where the selection started
+                    if self.mark.is_none() {
+                        self.mark = Some(view.cursor);
+                    }
+                    view.cursor = get_next_cursor(&view.cursor, &open.buffer, direction, view.display.wrap_width());
+                },
+                Some(Input::Key(key)) => {
+                    match key {
+                        Key::Ctrl('q')       => {
+                            match first_modified {
+                                None => return false,
+                                // ask about what's on screen, if it has changes
+                                Some(index) if !open.buffer.modified => action = Some(Action::Show(index)),
+                                Some(_) => { },
+                            }
+                            self.closing = Some(Closing::Editor);
+                        },
+                        Key::Ctrl('w')       => {
+                            if open.buffer.modified {
+                                self.closing = Some(Closing::Buffer);
+                            } else {
+                                action = Some(Action::Close);
+                            }
+                        },
+                        Key::Ctrl('n')       => action = Some(Action::Show((current + 1) % count)),
+                        Key::Ctrl('p')       => action = Some(Action::Show((current + count - 1) % count)),
+                        Key::Ctrl('o')       => self.opening = Some(String::new()),
+                        Key::Ctrl('b')       => action = Some(Action::Pick),
+                        Key::Ctrl('k')       => self.arranging = true,
+                        Key::Ctrl('s')       => {
+                            view.display.message = Some(match io::save_buffer(&open.path, &open.buffer) {
+                                Ok(()) => {
+                                    open.buffer.mark_saved();
+                                    open.swap.remove(open.buffer.revision);
+                                    format!("Saved {} lines", open.buffer.count_lines())
+                                },
+                                Err(error) => format!("Couldn't save: {}", error),
+                            });
+                        },
+                        Key::Ctrl('e')       => {
+                            view.cursor = Cursor::new( open.buffer.get_line_length(view.cursor.y), view.cursor.y);
+                        },
+                        Key::Ctrl('a')       => { view.cursor = Cursor::new( 0, view.cursor.y); }
+                        Key::Esc             => { self.mark = None; }
+                        Key::Ctrl('t')       => {
+                            view.display.wrap = !view.display.wrap;
+                            view.display.horizontal_offset = 0;
+                            view.display.message = Some(if view.display.wrap {
+                                "Wrapping long lines".to_string()
+                            } else {
+                                "Not wrapping long lines".to_string()
+                            });
+                            buffer_changes = BufferChanges::Buffer;
+                        },
+                        Key::Ctrl('d')       => {
+                            let line_ending = match open.buffer.line_ending {
+                                LineEnding::Lf   => LineEnding::Crlf,
+                                LineEnding::Crlf => LineEnding::Lf,
+                            };
+                            open.buffer.set_line_ending(line_ending);
+                            view.display.message = Some(format!("Lines end with {} when saved", line_ending.name()));
+                        },
+                        Key::Ctrl('l')       => {
+                            view.display.gutter.numbering = view.display.gutter.numbering.next();
+                            view.display.message = Some(format!("Line numbers: {}",
+                                                           view.display.gutter.numbering.name()));
+                            buffer_changes = BufferChanges::Buffer;
+                        },
+                        Key::Ctrl('f') | Key::Ctrl('r') => {
+                            let direction = if key == Key::Ctrl('f') {
+                                Direction::Forward
+                            } else {
+                                Direction::Backward
+                            };
+                            self.mark = None;
+                            self.search = Some(Search::new(view.cursor, view.display.vertical_offset, direction));
+                            buffer_changes = BufferChanges::Buffer;
+                        },
+                        Key::Ctrl('\\')      => {
+                            // with a selection only what's inside it is replaced
+                            let region = self.mark.take().map(|mark| Region::new(mark, view.cursor));
+                            self.replace = Some(Replace::new(view.cursor, region));
+                            buffer_changes = BufferChanges::Buffer;
+                        },
+                        Key::Ctrl('c')       => {
+                            if let Some(mark) = self.mark {
+                                let text = open.buffer.copy_region(&Region::new(mark, view.cursor));
+                                if let Err(error) = self.clipboard.copy(text) {
+                                    view.display.message = Some(format!("Couldn't copy: {}", error));
+                                }
+                            }
+                        },
+                        Key::Ctrl('x') if self.mark.is_some() => {
+                            let region = Region::new(self.mark.take().unwrap(), view.cursor);
+                            let (changes, text) = open.buffer.cut_region(&region);
+                            if let Err(error) = self.clipboard.copy(text) {
+                                view.display.message = Some(format!("Couldn't copy: {}", error));
+                            }
+                            buffer_changes = changes;
+                            view.cursor = region.start;
+                        },
+                        Key::Ctrl('v')       => {
+                            let text = self.clipboard.paste().replace("\r\n", "\n");
+                            // pasting replaces the selection
+                            let (changes, new_cursor) = match self.mark.take() {
+                                Some(mark) => open.buffer.replace_region(&Region::new(mark, view.cursor), &text),
+                                None => open.buffer.insert_string(&view.cursor, &text),
+                            };
+                            buffer_changes = changes;
+                            view.cursor = new_cursor;
+                        },
+                        Key::Backspace | Key::Delete if self.mark.is_some() => {
+                            let region = Region::new(self.mark.take().unwrap(), view.cursor);
+                            buffer_changes = open.buffer.delete_region(&region);
+                            view.cursor = region.start;
+                        },
+                        Key::Up | Key::Down | Key::Left | Key::Right => {
+                            self.mark = None;
+                            view.cursor = get_next_cursor(&view.cursor, &open.buffer, key, view.display.wrap_width());
+                        },
+                        _ => {
+                            self.mark = None;
+                            let (changes, new_cursor) = apply_command(key, &mut open.buffer, &view.cursor);
+                            buffer_changes = changes;
+                            view.cursor = new_cursor;
+                        },
+                    }
+                },
+                Some(Input::Resize) | None => { },
+            }
+        }
+
+        if had_selection || self.mark.is_some() {
+            buffer_changes = BufferChanges::Buffer;
+        }
+
+        // whether the views were moved around, and all of them need drawing
+        let mut rearranged = false;
+        let mut message = None;
+        match action {
+            Some(Action::Show(index)) => {
+                self.switch_to(index);
+                buffer_changes = BufferChanges::Buffer;
+            },
+            Some(Action::Open(path)) => {
+                match self.load(path) {
+                    Ok(index) => {
+                        self.switch_to(index);
+                        buffer_changes = BufferChanges::Buffer;
+                    },
+                    Err(Error::Read(_, error)) => message = Some(format!("Couldn't open: {}", error)),
+                    Err(error) => message = Some(error.to_string()),
+                }
+            },
+            Some(Action::Pick) => {
+                let names = self.buffers.iter().map(|open| {
+                    let modified = if open.buffer.modified { " [+]" } else { "" };
+                    format!("{}{}", open.shown_filename, modified)
+                }).collect();
+                let current = self.views[self.focused].buffer;
+                self.picker = Some(Picker::new("Buffer", names, current));
+                buffer_changes = BufferChanges::Buffer;
+            },
+            Some(Action::Close) => {
+                if !self.close() {
+                    return false;
+                }
+                rearranged = true;
+            },
+            Some(Action::Split(orientation)) => {
+                match self.split(orientation) {
+                    Ok(()) => rearranged = true,
+                    Err(error) => message = Some(error),
+                }
+            },
+            Some(Action::Focus(direction)) => {
+                let (areas, _) = self.arrange();
+                if let Some(view) = layout::neighbour(&areas, self.focused, direction) {
+                    self.focus(view);
+                    rearranged = true;
+                }
+            },
+            Some(Action::FocusNext) => {
+                let next = (self.focused + 1) % self.views.len();
+                self.focus(next);
+                rearranged = true;
+            },
+            Some(Action::Resize(amount)) => {
+                let screen = self.screen();
+                rearranged = self.layout.resize(self.focused, amount, screen);
+            },
+            Some(Action::CloseView) => {
+                if self.layout.remove(self.focused) {
+                    let focused = self.focused;
+                    self.leave(focused);
+                    self.views.remove(focused);
+                    let next = focused.min(self.views.len() - 1);
+                    self.focus(next);
+                    rearranged = true;
+                } else {
+                    message = Some("Can't close the only view".to_string());
+                }
+            },
+            None => { },
+        }
+
+        self.update_swaps();
+        if let Some(message) = message {
+            self.views[self.focused].display.message = Some(message);
+        }
+        if rearranged {
+            self.redraw();
+        } else {
+            self.render(buffer_changes);
+        }
+        true
+    }
+
+    // Unsaved changes go to the swap file a little after they're made, and
+    // once they're undone it goes away.
+    fn update_swaps(&mut self) {
+        for open in &mut self.buffers {
+            open.swap.update(open.buffer.revision);
+            if !open.swap.is_due() {
+                continue;
+            }
+            if open.buffer.modified {
+                let buffer = &open.buffer;
+                if let Err(error) = open.swap.write(buffer.revision, |file| buffer.write_to(file)) {
+                    let message = format!("Couldn't write {}: {}", open.swap.path().display(), error);
+                    self.views[self.focused].display.message = Some(message);
+                }
+            } else {
+                open.swap.remove(open.buffer.revision);
+            }
+        }
+    }
+
+    // Draws the changes to the focused view's buffer in every view that
+    // shows it, the focused one last so the cursor ends up there.
+    fn render(&mut self, buffer_changes: BufferChanges) {
+        let buffer = self.views[self.focused].buffer;
+        for view in 0..self.views.len() {
+            if view != self.focused && self.views[view].buffer == buffer {
+                self.render_view(view, buffer_changes.clone());
+            }
+        }
+        let focused = self.focused;
+        self.render_view(focused, buffer_changes);
+        self.views[focused].display.flush();
+    }
+
+    // Shares the screen out between the views again and draws all of it.
+    fn redraw(&mut self) {
+        let (areas, separators) = self.arrange();
+        for (view, area) in areas {
+            self.views[view].display.place(area);
+        }
+        let display = &self.views[self.focused].display;
+        let face = display.theme.face(Class::Gutter);
+        for separator in separators {
+            for y in separator.y..separator.y + separator.height {
+                display.backend.print_char(separator.x, y, face, '│');
+            }
+        }
+        for view in 0..self.views.len() {
+            if view != self.focused {
+                self.render_view(view, BufferChanges::Buffer);
+            }
+        }
+        self.render(BufferChanges::Buffer);
+    }
+
+    // Draws view `index` after `buffer_changes` to its buffer. Only the
+    // focused view shows the prompt, the selection and the search matches,
+    // and gets the cursor.
+    fn render_view(&mut self, index: usize, mut buffer_changes: BufferChanges) {
+        let count = self.buffers.len();
+        let modified = self.buffers.iter().filter(|open| open.buffer.modified).count();
+        let focused = index == self.focused;
+        let view = &mut self.views[index];
+        let open = &mut self.buffers[view.buffer];
+        if focused {
+            let closing = match self.closing {
+                Some(Closing::Buffer) => Some(format!("Save changes to {}? (y/n/c)", open.shown_filename)),
+                Some(Closing::Editor) if modified > 1 => Some(format!("Save changes to {} files? (y/n/c)", modified)),
+                Some(Closing::Editor) => Some("Save changes? (y/n/c)".to_string()),
+                None => None,
+            };
+            let (search, replace, opening, picker) = (&self.search, &self.replace, &self.opening, &self.picker);
+            let arranging = if self.arranging { Some(ARRANGING_PROMPT.to_string()) } else { None };
+            view.display.prompt = open.recovery.as_ref().map(|_| recovery_prompt(&open.swap))
+                .or_else(|| search.as_ref().map(Search::prompt))
+                .or_else(|| replace.as_ref().map(Replace::prompt))
+                .or_else(|| opening.as_ref().map(|typed| format!("Open: {}", typed)))
+                .or_else(|| picker.as_ref().map(Picker::prompt))
+                .or(closing)
+                .or(arranging);
+            view.display.search_query = self.search.as_ref().map(|search| search.query.clone());
+            let cursor = view.cursor;
+            view.display.selection = match self.replace {
+                Some(ref active) => active.current_match(&open.buffer),
+                None => self.mark.map(|mark| Region::new(mark, cursor)),
+            };
+        } else {
+            // an edit in another view may have left the cursor past the end
+            let y = view.cursor.y.min(open.buffer.count_lines().saturating_sub(1));
+            view.cursor = Cursor::new(view.cursor.x.min(open.buffer.get_line_length(y)), y);
+            view.display.prompt = None;
+            view.display.message = None;
+            view.display.search_query = None;
+            view.display.selection = None;
+        }
+
+        if view.display.gutter.update(open.buffer.count_lines(), view.cursor.y) {
+            buffer_changes = BufferChanges::Buffer;
+        }
+
+        if view.display.scroll_to(&open.buffer, &view.cursor) {
+            buffer_changes = BufferChanges::Buffer;
+        }
+
+        open.buffer.highlight_to(view.display.vertical_offset + view.display.text_height());
+        match self.picker {
+            Some(ref picker) if focused => {
+                let (lines, selected) = picker.lines();
+                view.display.render_list(&lines, selected);
+            },
+            _ => view.display.render_buffer_changes(&open.buffer, buffer_changes),
+        }
+        // which of the open files this is, when there's more than one
+        let filename = if count > 1 {
+            format!("[{}/{}] {}", view.buffer + 1, count, open.shown_filename)
+        } else {
+            open.shown_filename.clone()
+        };
+        view.display.render_status(&filename, &open.buffer, &view.cursor);
+        if focused {
+            view.display.render_cursor(&open.buffer, &view.cursor);
+            view.display.render_prompt();
+        }
+    }
+}
+
+const ARRANGING_PROMPT: &str = "View: s/v split, arrows/o move, +/- resize, c close";
+
+fn recovery_prompt(swap: &Swap) -> String {
+    let name = swap.path().file_name().unwrap_or_default().to_string_lossy();
+    format!("{} has unsaved changes, recover them? (y/n)", name)
+}
+
+// Saves every buffer with unsaved changes, stopping at the first one that
+// can't be.
+fn save_all(buffers: &mut [OpenBuffer]) -> Result<(), String> {
+    for open in buffers.iter_mut().filter(|open| open.buffer.modified) {
+        io::save_buffer(&open.path, &open.buffer)
+            .map_err(|error| format!("Couldn't save {}: {}", open.shown_filename, error))?;
+        open.buffer.mark_saved();
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use grid::Grid;
+    use gutter::{Gutter, Numbering};
+    use std::rc::Rc;
+    use theme::{Class, Palette, Theme};
+
+    // An editor on `text` drawing to a Grid, without line numbers.
+    fn headless(text: &str, width: usize, height: usize) -> (Editor, Rc<Grid>) {
+        let theme = Theme::built_in("dark", Palette::Basic).unwrap();
+        let grid = Rc::new(Grid::new(width, height, theme.face(Class::Text)));
+        let mut display = Display::new(Box::new(grid.clone()), theme);
+        display.gutter = Gutter::new(Numbering::Off);
+        let mut editor = Editor::new(display, Clipboard::new(), Languages::built_in());
+        editor.open(PathBuf::from("/nonexistent/editor2/test.txt")).unwrap();
+        editor.buffers[0].buffer = Buffer::from_string(text);
+        editor.show(0);
+        (editor, grid)
+    }
+
+    // handles the keys waiting in the grid, returns false if they quit
+    fn play(editor: &mut Editor, grid: &Grid) -> bool {
+        while grid.has_events() {
+            if !editor.step() {
+                return false;
+            }
+        }
+        true
+    }
+
+    #[test]
+    fn test_typing_shows_on_screen() {
+        let (mut editor, grid) = headless("one\n", 70, 4);
+        let status = |text: &str| format!(" /nonexistent/editor2/test.txt{}", text);
+        assert_eq!(grid.rows(), vec!["one", "", "", &status("         Text  LF  Ln 1, Col 1  1 lines")]);
+        assert_eq!(grid.cursor(), Some((0, 0)));
+
+        grid.push_key(Key::Ctrl('e'));
+        grid.type_text("\ntwo\nthree");
+        assert!(play(&mut editor, &grid));
+        assert_eq!(grid.rows(), vec!["one", "two", "three", &status(" [+]     Text  LF  Ln 3, Col 6  3 lines")]);
+        assert_eq!(grid.cursor(), Some((5, 2)));
+        assert_eq!(grid.face(0, 3), editor.views[0].display.theme.face(Class::Status));
+
+        // a selection with shift+arrows, as the terminal sends it
+        for _ in 0..2 {
+            grid.push_key(Key::Esc);
+            grid.type_text("[1;2D");
+        }
+        assert!(play(&mut editor, &grid));
+        assert_eq!(grid.face(2, 2), editor.views[0].display.theme.face(Class::Text));
+        assert_eq!(grid.face(3, 2), editor.views[0].display.theme.face(Class::Selection));
+        assert_eq!(grid.cursor(), Some((3, 2)));
+    }
+
+    #[test]
+    fn test_quitting_asks_to_save() {
+        let (mut editor, grid) = headless("", 40, 3);
+        grid.push_key(Key::Char('x'));
+        grid.push_key(Key::Ctrl('q'));
+        assert!(play(&mut editor, &grid));
+        assert_eq!(grid.row(2).trim_end(), "Save changes? (y/n/c)");
+        assert_eq!(grid.cursor(), Some((21, 2)));
+
+        grid.push_key(Key::Char('c'));
+        assert!(play(&mut editor, &grid));
+        assert_eq!(grid.row(2).trim_end(), " /nonexistent/editor2/test.txt [+]");
+        assert_eq!(grid.row(0).trim_end(), "x");
+
+        grid.push_key(Key::Ctrl('q'));
+        grid.push_key(Key::Char('n'));
+        assert!(!play(&mut editor, &grid));
+    }
+
+    #[test]
+    fn test_typing_the_start_of_a_raw_string() {
+        let (mut editor, grid) = headless("", 40, 3);
+        editor.open(PathBuf::from("/nonexistent/editor2/main.rs")).unwrap();
+        grid.type_text("r##");
+        assert!(play(&mut editor, &grid));
+        assert_eq!(grid.row(0).trim_end(), "r##");
+        grid.type_text("\"x\"##");
+        assert!(play(&mut editor, &grid));
+        assert_eq!(grid.face(3, 0), editor.views[0].display.theme.face(Class::String));
+    }
+
+    #[test]
+    fn test_switching_buffers() {
+        let (mut editor, grid) = headless("one\ntwo\n", 70, 4);
+        grid.push_key(Key::Down);
+        assert!(play(&mut editor, &grid));
+        editor.open(PathBuf::from("/nonexistent/editor2/other.rs")).unwrap();
+        assert_eq!(grid.row(3).trim_end(), format!(" [2/2] /nonexistent/editor2/other.rs{}",
+                                                   "   Rust  LF  Ln 1, Col 1  0 lines"));
+        grid.type_text("x");
+        assert!(play(&mut editor, &grid));
+
+        // each one keeps its own cursor and changes
+        grid.push_key(Key::Ctrl('n'));
+        assert!(play(&mut editor, &grid));
+        assert_eq!(grid.rows()[..2], ["one", "two"]);
+        assert!(grid.row(3).starts_with(" [1/2] /nonexistent/editor2/test.txt "));
+        assert_eq!(grid.cursor(), Some((0, 1)));
+        grid.push_key(Key::Ctrl('p'));
+        assert!(play(&mut editor, &grid));
+        assert_eq!(grid.row(0).trim_end(), "x");
+        assert!(grid.row(3).starts_with(" [2/2] /nonexistent/editor2/other.rs [+] "));
+        assert_eq!(grid.cursor(), Some((1, 0)));
+
+        // opening it again goes back to it
+        grid.push_key(Key::Ctrl('o'));
+        grid.type_text("/nonexistent/editor2/test.txt");
+        assert!(play(&mut editor, &grid));
+        assert_eq!(grid.row(3).trim_end(), "Open: /nonexistent/editor2/test.txt");
+        grid.push_key(Key::Enter);
+        assert!(play(&mut editor, &grid));
+        assert_eq!(editor.buffers.len(), 2);
+        assert_eq!(grid.row(0).trim_end(), "one");
+    }
+
+    #[test]
+    fn test_picking_and_closing_buffers() {
+        let (mut editor, grid) = headless("one\n", 60, 4);
+        editor.open(PathBuf::from("/nonexistent/editor2/b.txt")).unwrap();
+        editor.open(PathBuf::from("/nonexistent/editor2/c.txt")).unwrap();
+        grid.type_text("x");
+        grid.push_key(Key::Ctrl('b'));
+        assert!(play(&mut editor, &grid));
+        assert_eq!(grid.rows(), vec![" /nonexistent/editor2/test.txt", " /nonexistent/editor2/b.txt",
+                                     " /nonexistent/editor2/c.txt [+]", "Buffer:"]);
+        assert_eq!(grid.face(0, 2), editor.views[0].display.theme.face(Class::Selection));
+        grid.type_text("b.");
+        assert!(play(&mut editor, &grid));
+        assert_eq!(grid.rows()[..2], [" /nonexistent/editor2/b.txt", ""]);
+        grid.push_key(Key::Enter);
+        assert!(play(&mut editor, &grid));
+        assert!(grid.row(3).starts_with(" [2/3] /nonexistent/editor2/b.txt "));
+
+        // closing moves on to the next one, and asks first if it's changed
+        grid.push_key(Key::Ctrl('w'));
+        assert!(play(&mut editor, &grid));
+        assert!(grid.row(3).starts_with(" [2/2] /nonexistent/editor2/c.txt [+] "));
+        grid.push_key(Key::Ctrl('w'));
+        assert!(play(&mut editor, &grid));
+        assert_eq!(grid.row(3).trim_end(), "Save changes to /nonexistent/editor2/c.txt? (y/n/c)");
+        grid.push_key(Key::Char('n'));
+        assert!(play(&mut editor, &grid));
+        assert_eq!(grid.rows()[0], "one");
+        assert!(grid.row(3).starts_with(" /nonexistent/editor2/test.txt "));
+
+        // and the last one quits
+        grid.push_key(Key::Ctrl('w'));
+        assert!(!play(&mut editor, &grid));
+    }
+
+    #[test]
+    fn test_views_on_the_same_buffer() {
+        let (mut editor, grid) = headless("one\ntwo\n", 80, 8);
+        grid.push_key(Key::Ctrl('k'));
+        assert!(play(&mut editor, &grid));
+        assert!(grid.row(7).starts_with("View: "));
+        grid.push_key(Key::Char('s'));
+        grid.push_key(Key::Down);
+        grid.type_text("x");
+        assert!(play(&mut editor, &grid));
+        // the new view below gets the keys, the one above shows the edit too
+        assert_eq!(grid.rows()[..3], ["one", "xtwo", ""]);
+        assert_eq!(grid.rows()[4..6], ["one", "xtwo"]);
+        assert!(grid.row(3).starts_with(" /nonexistent/editor2/test.txt [+]"));
+        assert_eq!(grid.cursor(), Some((1, 5)));
+
+        // each with its own cursor
+        grid.push_key(Key::Ctrl('k'));
+        grid.push_key(Key::Up);
+        grid.type_text("y");
+        assert!(play(&mut editor, &grid));
+        assert_eq!(grid.rows()[..2], ["yone", "xtwo"]);
+        assert_eq!(grid.rows()[4..6], ["yone", "xtwo"]);
+        assert_eq!(grid.cursor(), Some((1, 0)));
+
+        grid.push_key(Key::Ctrl('k'));
+        grid.push_key(Key::Char('+'));
+        assert!(play(&mut editor, &grid));
+        assert!(grid.row(4).starts_with(" /nonexistent/editor2/test.txt [+]"));
+        assert_eq!(grid.row(5).trim_end(), "yone");
+
+        grid.push_key(Key::Ctrl('k'));
+        grid.push_key(Key::Char('c'));
+        assert!(play(&mut editor, &grid));
+        assert_eq!(grid.rows()[..3], ["yone", "xtwo", ""]);
+        assert!(grid.row(7).starts_with(" /nonexistent/editor2/test.txt [+]"));
+        grid.push_key(Key::Ctrl('k'));
+        grid.push_key(Key::Char('c'));
+        assert!(play(&mut editor, &grid));
+        assert!(grid.row(7).contains("Can't close the only view"));
+    }
+
+    #[test]
+    fn test_views_side_by_side() {
+        let (mut editor, grid) = headless("one\n", 21, 3);
+        editor.open(PathBuf::from("/nonexistent/editor2/b.txt")).unwrap();
+        grid.push_key(Key::Ctrl('k'));
+        grid.push_key(Key::Char('v'));
+        grid.push_key(Key::Ctrl('p'));
+        assert!(play(&mut editor, &grid));
+        assert_eq!(grid.row(0).trim_end(), "          │one");
+        assert_eq!(grid.cursor(), Some((11, 0)));
+
+        grid.push_key(Key::Ctrl('k'));
+        grid.push_key(Key::Left);
+        grid.type_text("b");
+        assert!(play(&mut editor, &grid));
+        assert_eq!(grid.row(0).trim_end(), "b         │one");
+        assert_eq!(grid.cursor(), Some((1, 0)));
+        assert_eq!(grid.row(2), " [2/2] /no│ [1/2] /no");
+    }
+
+    #[test]
+    fn test_resizing_the_screen() {
+        let text: String = (1..11).map(|line| format!("line {}\n", line)).collect();
+        let (mut editor, grid) = headless(&text, 40, 6);
+        grid.push_key(Key::Ctrl('k'));
+        grid.push_key(Key::Char('v'));
+        for _ in 0..7 {
+            grid.push_key(Key::Down);
+        }
+        assert!(play(&mut editor, &grid));
+        assert_eq!(grid.row(0).trim_end(), "line 1              │line 4");
+        assert_eq!(grid.cursor(), Some((21, 4)));
+
+        // the views share the new size, and the cursor stays on screen
+        grid.resize(30, 3);
+        assert!(play(&mut editor, &grid));
+        assert_eq!(grid.rows()[..2], ["line 1         │line 7", "line 2         │line 8"]);
+        assert_eq!(grid.cursor(), Some((16, 1)));
+
+        grid.resize(50, 12);
+        assert!(play(&mut editor, &grid));
+        assert_eq!(grid.row(0).trim_end(), "line 1                   │line 7");
+        assert_eq!(grid.row(10).trim_end(), "                         │");
+        assert_eq!(grid.row(11), " /nonexistent/editor2/tes│ /nonexistent/editor2/te");
+        assert_eq!(grid.cursor(), Some((26, 1)));
+    }
+
+    #[test]
+    fn test_cancelling_replace_says_nothing() {
+        let (mut editor, grid) = headless("one\n", 40, 3);
+        grid.push_key(Key::Ctrl('\\'));
+        grid.type_text("on");
+        grid.push_key(Key::Esc);
+        assert!(play(&mut editor, &grid));
+        assert_eq!(grid.row(2).trim_end(), " /nonexistent/editor2/test.txt");
+        assert_eq!(grid.row(0).trim_end(), "one");
+    }
+
+    #[test]
+    fn test_pasting_over_a_selection_undoes_in_one_step() {
+        let (mut editor, grid) = headless("one two\n", 40, 3);
+        editor.clipboard.copy("six".to_string()).unwrap();
+        grid.push_key(Key::Ctrl('e'));
+        for _ in 0..3 {
+            grid.push_key(Key::Esc);
+            grid.type_text("[1;2D");
+        }
+        grid.push_key(Key::Ctrl('v'));
+        assert!(play(&mut editor, &grid));
+        assert_eq!(grid.row(0).trim_end(), "one six");
+
+        grid.push_key(Key::Ctrl('z'));
+        assert!(play(&mut editor, &grid));
+        assert_eq!(grid.row(0).trim_end(), "one two");
+        assert_eq!(editor.buffers[0].buffer.get_line(0), "one two");
+    }
+
+    #[test]
+    fn test_wrapping_a_line_taller_than_the_screen() {
+        let (mut editor, grid) = headless("aaaaaaaaaabbbbbbbbbbccccccccccddddddddddeeeee\nnext\n", 10, 4);
+        grid.push_key(Key::Ctrl('t'));
+        grid.push_key(Key::Ctrl('e'));
+        assert!(play(&mut editor, &grid));
+        // the line takes five rows and there's room for three
+        assert_eq!(grid.rows()[..3], ["cccccccccc", "dddddddddd", "eeeee"]);
+        assert_eq!(grid.cursor(), Some((5, 2)));
+
+        grid.push_key(Key::Ctrl('a'));
+        assert!(play(&mut editor, &grid));
+        assert_eq!(grid.rows()[..3], ["aaaaaaaaaa", "bbbbbbbbbb", "cccccccccc"]);
+        assert_eq!(grid.cursor(), Some((0, 0)));
+
+        grid.push_key(Key::Ctrl('e'));
+        grid.push_key(Key::Right);
+        assert!(play(&mut editor, &grid));
+        assert_eq!(grid.rows()[..2], ["next", ""]);
+        assert_eq!(grid.cursor(), Some((0, 0)));
+    }
+}
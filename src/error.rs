@@ -0,0 +1,85 @@
+//! Errors that stop the editor.
+//!
+//! What goes wrong while editing, like a file that can't be saved, is shown
+//! in the status bar and we carry on. What's left are the errors we can't
+//! start with, and they go up to main, which prints them once the terminal is
+//! back to normal.
+//!
+//! A panic would print its message on top of the screen, in raw mode, right
+//! before RustBox resets the terminal and wipes it. Our hook keeps the message
+//! instead, for main to print after unwinding has dropped the RustBox.
+
+use std::backtrace::{Backtrace, BacktraceStatus};
+use std::fmt;
+use std::io;
+use std::panic;
+use std::sync::Mutex;
+
+use rustbox::InitError;
+
+static PANIC: Mutex<Option<String>> = Mutex::new(None);
+
+#[derive(Debug)]
+pub enum Error {
+    // termbox couldn't take over the terminal
+    Terminal(InitError),
+    // the file exists but we can't read it
+    Read(String, io::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Terminal(ref error) => write!(formatter, "can't use the terminal: {}", error),
+            Error::Read(ref filename, ref error) => write!(formatter, "can't read {}: {}", filename, error),
+        }
+    }
+}
+
+impl From<InitError> for Error {
+    fn from(error: InitError) -> Error {
+        Error::Terminal(error)
+    }
+}
+
+pub fn set_panic_hook() {
+    panic::set_hook(Box::new(|info| {
+        let payload = info.payload();
+        let message = match (payload.downcast_ref::<&str>(), payload.downcast_ref::<String>()) {
+            (Some(message), _) => message.to_string(),
+            (_, Some(message)) => message.clone(),
+            _ => "unknown error".to_string(),
+        };
+        let mut report = match info.location() {
+            Some(location) => format!("{} at {}:{}", message, location.file(), location.line()),
+            None => message,
+        };
+        // only with RUST_BACKTRACE set, like the default hook
+        let backtrace = Backtrace::capture();
+        if backtrace.status() == BacktraceStatus::Captured {
+            report = format!("{}\n{}", report, backtrace);
+        }
+        *PANIC.lock().unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(report);
+    }));
+}
+
+/// what the last panic was about
+pub fn panic_report() -> String {
+    let report = PANIC.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).take();
+    report.unwrap_or_else(|| "unknown error".to_string())
+}
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_error_messages() {
+        let error = Error::from(InitError::UnsupportedTerminal);
+        assert_eq!(error.to_string(), "can't use the terminal: Unsupported terminal");
+        let error = Error::Read("file.txt".to_string(), io::Error::other("not UTF-8"));
+        assert_eq!(error.to_string(), "can't read file.txt: not UTF-8");
+    }
+}
@@ -0,0 +1,165 @@
+// Saving files safely.
+//
+// The text goes to a temporary file next to the original, which is flushed
+// to disk and then renamed over it, so a crash or a full disk halfway through
+// leaves the original as it was. The new file gets the original's permissions
+// and, when we're allowed to set them, its owner and group.
+//
+// When the owner can't be kept, or the file has other hard links that a
+// rename would leave pointing at the old text, the file is overwritten in
+// place instead.
+
+use std::fs::{self, File, OpenOptions};
+use std::io;
+use std::os::unix::fs::{self as unix, MetadataExt};
+use std::path::{Path, PathBuf};
+use std::process;
+
+pub fn save<F>(path: &Path, write: F) -> io::Result<()>
+    where F: Fn(&mut File) -> io::Result<()>
+{
+    // a symlink stays one, the file it points to is what changes
+    let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let original = fs::metadata(&path).ok();
+    if original.as_ref().is_some_and(|metadata| metadata.nlink() > 1) {
+        return overwrite(&path, &write);
+    }
+
+    let (temporary, mut file) = create_temporary(&path)?;
+    let saved = replace(&path, &temporary, &mut file, original.as_ref(), &write);
+    if saved.is_err() {
+        let _ = fs::remove_file(&temporary);
+    }
+    match saved {
+        Ok(true) => Ok(()),
+        // the rename would have given the file to us
+        Ok(false) => overwrite(&path, &write),
+        Err(error) => Err(error),
+    }
+}
+
+// Writes the temporary file and renames it over `path`. Returns false, and
+// leaves `path` alone, if the new file can't have the original's owner.
+fn replace<F>(path: &Path, temporary: &Path, file: &mut File, original: Option<&fs::Metadata>, write: F)
+              -> io::Result<bool>
+    where F: Fn(&mut File) -> io::Result<()>
+{
+    write(file)?;
+    if let Some(original) = original {
+        fs::set_permissions(temporary, original.permissions())?;
+        let created = file.metadata()?;
+        if (created.uid(), created.gid()) != (original.uid(), original.gid())
+            && unix::chown(temporary, Some(original.uid()), Some(original.gid())).is_err() {
+            fs::remove_file(temporary)?;
+            return Ok(false);
+        }
+    }
+    file.sync_all()?;
+    fs::rename(temporary, path)?;
+    // make the rename itself stick, not every system lets us
+    if let Some(dir) = path.parent() {
+        let _ = File::open(dir).and_then(|dir| dir.sync_all());
+    }
+    Ok(true)
+}
+
+fn overwrite<F>(path: &Path, write: F) -> io::Result<()>
+    where F: Fn(&mut File) -> io::Result<()>
+{
+    let mut file = OpenOptions::new().write(true).truncate(true).create(true).open(path)?;
+    write(&mut file)?;
+    file.sync_all()
+}
+
+// .name.<pid>-<n>.tmp in the same directory, so renaming it doesn't cross
+// file systems
+pub(crate) fn create_temporary(path: &Path) -> io::Result<(PathBuf, File)> {
+    let name = path.file_name().ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "not a file"))?;
+    let name = name.to_string_lossy();
+    let mut attempt = 0;
+    loop {
+        let temporary = path.with_file_name(format!(".{}.{}-{}.tmp", name, process::id(), attempt));
+        match OpenOptions::new().write(true).create_new(true).open(&temporary) {
+            Ok(file) => return Ok((temporary, file)),
+            Err(ref error) if error.kind() == io::ErrorKind::AlreadyExists && attempt < 100 => attempt += 1,
+            Err(error) => return Err(error),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use std::env;
+    use std::io::Write;
+    use std::os::unix::fs::PermissionsExt;
+
+    // an empty directory of its own for each test
+    fn directory(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("editor2-{}-{}", name, process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn write_text(text: &'static str) -> impl Fn(&mut File) -> io::Result<()> {
+        move |file: &mut File| file.write_all(text.as_bytes())
+    }
+
+    fn files_in(dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(dir).unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn test_save_replaces_the_file_and_keeps_permissions() {
+        let dir = directory("permissions");
+        let path = dir.join("file.txt");
+        save(&path, write_text("new file")).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new file");
+
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o640)).unwrap();
+        save(&path, write_text("changed")).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "changed");
+        assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o640);
+        // nothing left behind
+        assert_eq!(files_in(&dir), vec!["file.txt"]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_failed_save_leaves_the_original() {
+        let dir = directory("failure");
+        let path = dir.join("file.txt");
+        fs::write(&path, "original").unwrap();
+        let failing = |file: &mut File| {
+            file.write_all(b"half")?;
+            Err(io::Error::other("disk full"))
+        };
+        assert_eq!(save(&path, failing).unwrap_err().to_string(), "disk full");
+        assert_eq!(fs::read_to_string(&path).unwrap(), "original");
+        assert_eq!(files_in(&dir), vec!["file.txt"]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_links_are_kept() {
+        let dir = directory("links");
+        let path = dir.join("file.txt");
+        fs::write(&path, "original").unwrap();
+        unix::symlink(&path, dir.join("symlink")).unwrap();
+        save(&dir.join("symlink"), write_text("through the symlink")).unwrap();
+        assert!(fs::symlink_metadata(dir.join("symlink")).unwrap().file_type().is_symlink());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "through the symlink");
+
+        fs::hard_link(&path, dir.join("hard")).unwrap();
+        save(&path, write_text("for both")).unwrap();
+        assert_eq!(fs::read_to_string(dir.join("hard")).unwrap(), "for both");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
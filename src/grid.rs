@@ -0,0 +1,167 @@
+//! A screen in memory, for tests.
+//!
+//! It draws like termbox does, to a back buffer that's shown on present(), and
+//! hands out the keys it was given in order, so a test can type at the editor
+//! and then look at exactly what's on screen. It can be resized like a
+//! terminal window too.
+
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use backend::{Backend, Event, Key};
+use theme::Face;
+
+/// Once the events run out, polling gives Event::None instead of waiting.
+pub struct Grid {
+    width: Cell<usize>,
+    height: Cell<usize>,
+    blank: Face,
+    // what's being drawn, and what was on screen at the last present()
+    drawing: RefCell<Vec<(char, Face)>>,
+    shown: RefCell<Vec<(char, Face)>>,
+    cursor: Cell<Option<(usize, usize)>>,
+    shown_cursor: Cell<Option<(usize, usize)>>,
+    events: RefCell<VecDeque<Event>>,
+}
+
+impl Grid {
+    /// cleared cells look like `blank`
+    pub fn new(width: usize, height: usize, blank: Face) -> Grid {
+        let cells = vec![(' ', blank); width * height];
+        Grid {
+            width: Cell::new(width),
+            height: Cell::new(height),
+            blank,
+            drawing: RefCell::new(cells.clone()),
+            shown: RefCell::new(cells),
+            cursor: Cell::new(None),
+            shown_cursor: Cell::new(None),
+            events: RefCell::new(VecDeque::new()),
+        }
+    }
+
+    pub fn push_key(&self, key: Key) {
+        self.events.borrow_mut().push_back(Event::Key(key));
+    }
+
+    /// each char as a key, newlines as Enter
+    pub fn type_text(&self, text: &str) {
+        for character in text.chars() {
+            self.push_key(if character == '\n' { Key::Enter } else { Key::Char(character) });
+        }
+    }
+
+    /// Changes size once the editor gets to the resize event, like termbox
+    /// does. The screen starts out blank again.
+    pub fn resize(&self, width: usize, height: usize) {
+        self.events.borrow_mut().push_back(Event::Resize(width, height));
+    }
+
+    pub fn has_events(&self) -> bool {
+        !self.events.borrow().is_empty()
+    }
+
+    /// the text of screen row `y`
+    pub fn row(&self, y: usize) -> String {
+        let width = self.width.get();
+        let shown = self.shown.borrow();
+        shown[y * width..(y + 1) * width].iter().map(|&(character, _)| character).collect()
+    }
+
+    /// every row, without the blanks at their ends
+    pub fn rows(&self) -> Vec<String> {
+        (0..self.height.get()).map(|y| self.row(y).trim_end().to_string()).collect()
+    }
+
+    pub fn face(&self, x: usize, y: usize) -> Face {
+        self.shown.borrow()[y * self.width.get() + x].1
+    }
+
+    pub fn cursor(&self) -> Option<(usize, usize)> {
+        self.shown_cursor.get()
+    }
+}
+
+impl Backend for Grid {
+    fn width(&self) -> usize {
+        self.width.get()
+    }
+
+    fn height(&self) -> usize {
+        self.height.get()
+    }
+
+    // like termbox, what doesn't fit is dropped
+    fn print_char(&self, x: usize, y: usize, face: Face, character: char) {
+        if x < self.width.get() && y < self.height.get() {
+            self.drawing.borrow_mut()[y * self.width.get() + x] = (character, face);
+        }
+    }
+
+    fn set_cursor(&self, position: Option<(usize, usize)>) {
+        self.cursor.set(position);
+    }
+
+    fn clear(&self) {
+        for cell in self.drawing.borrow_mut().iter_mut() {
+            *cell = (' ', self.blank);
+        }
+    }
+
+    fn present(&self) {
+        self.shown.borrow_mut().clone_from(&self.drawing.borrow());
+        self.shown_cursor.set(self.cursor.get());
+    }
+
+    fn poll_event(&self, _timeout: Option<Duration>) -> Event {
+        let event = self.events.borrow_mut().pop_front().unwrap_or(Event::None);
+        if let Event::Resize(width, height) = event {
+            let cells = vec![(' ', self.blank); width * height];
+            self.width.set(width);
+            self.height.set(height);
+            *self.drawing.borrow_mut() = cells.clone();
+            *self.shown.borrow_mut() = cells;
+        }
+        event
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use backend::{Color, Style};
+
+    #[test]
+    fn test_grid_shows_what_was_presented() {
+        let blank = Face {style: Style::NORMAL, fg: Color::Default, bg: Color::Default};
+        let bold = Face {style: Style::BOLD, ..blank};
+        let grid = Grid::new(6, 2, blank);
+        grid.print(4, 0, bold, "long");
+        grid.set_cursor(Some((1, 1)));
+        assert_eq!(grid.rows(), vec!["", ""]);
+        assert_eq!(grid.cursor(), None);
+
+        grid.present();
+        assert_eq!(grid.rows(), vec!["    lo", ""]);
+        assert_eq!(grid.face(4, 0), bold);
+        assert_eq!(grid.cursor(), Some((1, 1)));
+        grid.clear();
+        grid.present();
+        assert_eq!(grid.row(0), "      ");
+
+        grid.type_text("a\n");
+        assert!(matches!(grid.poll_event(None), Event::Key(Key::Char('a'))));
+        assert!(matches!(grid.poll_event(None), Event::Key(Key::Enter)));
+        assert!(!grid.has_events());
+        assert!(matches!(grid.poll_event(None), Event::None));
+
+        grid.resize(3, 1);
+        assert_eq!(grid.width(), 6);
+        assert!(matches!(grid.poll_event(None), Event::Resize(3, 1)));
+        assert_eq!((grid.width(), grid.height()), (3, 1));
+        assert_eq!(grid.rows(), vec![""]);
+    }
+}
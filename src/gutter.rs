@@ -0,0 +1,148 @@
+// Line numbers on the left of the text.
+//
+// The gutter is as wide as the biggest line number plus a space. Numbers can
+// be absolute, relative to the cursor's line (handy to know how far to move)
+// or hybrid, which is relative except for the cursor's line. The starting mode
+// is taken from the environment:
+//
+//     EDITOR2_LINE_NUMBERS=off|absolute|relative|hybrid
+
+use std::env;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Numbering {
+    Off,
+    Absolute,
+    Relative,
+    Hybrid,
+}
+
+impl Numbering {
+    pub fn from_env() -> Numbering {
+        match env::var("EDITOR2_LINE_NUMBERS").as_ref().map(|name| &name[..]) {
+            Ok("off")      => Numbering::Off,
+            Ok("relative") => Numbering::Relative,
+            Ok("hybrid")   => Numbering::Hybrid,
+            _              => Numbering::Absolute,
+        }
+    }
+
+    // the mode after this one, to cycle through them
+    pub fn next(self) -> Numbering {
+        match self {
+            Numbering::Off      => Numbering::Absolute,
+            Numbering::Absolute => Numbering::Relative,
+            Numbering::Relative => Numbering::Hybrid,
+            Numbering::Hybrid   => Numbering::Off,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Numbering::Off      => "off",
+            Numbering::Absolute => "absolute",
+            Numbering::Relative => "relative",
+            Numbering::Hybrid   => "hybrid",
+        }
+    }
+}
+
+pub struct Gutter {
+    pub numbering: Numbering,
+    line_count: usize,
+    cursor_line: usize,
+}
+
+impl Gutter {
+    pub fn new(numbering: Numbering) -> Gutter {
+        Gutter {numbering, line_count: 0, cursor_line: 0}
+    }
+
+    pub fn width(&self) -> usize {
+        if self.numbering == Numbering::Off {
+            return 0;
+        }
+        self.line_count.max(1).to_string().len() + 1
+    }
+
+    // Returns whether every line has to be drawn again, because the gutter
+    // changed width or the numbers depend on a cursor that moved to another
+    // line.
+    pub fn update(&mut self, line_count: usize, cursor_line: usize) -> bool {
+        let width = self.width();
+        let moved = cursor_line != self.cursor_line;
+        self.line_count = line_count;
+        self.cursor_line = cursor_line;
+        let relative = self.numbering == Numbering::Relative || self.numbering == Numbering::Hybrid;
+        width != self.width() || (relative && moved)
+    }
+
+    // what goes in front of a line, blank past the end of the buffer
+    pub fn label(&self, line_number: usize) -> String {
+        let width = self.width();
+        if width == 0 {
+            return String::new();
+        }
+        if line_number >= self.line_count {
+            return " ".repeat(width);
+        }
+        let distance = line_number.abs_diff(self.cursor_line);
+        let number = match self.numbering {
+            Numbering::Relative => distance,
+            Numbering::Hybrid if distance != 0 => distance,
+            _ => line_number + 1,
+        };
+        format!("{:>1$} ", number, width - 1)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_width_follows_line_count() {
+        let mut gutter = Gutter::new(Numbering::Absolute);
+        assert_eq!(gutter.width(), 2);
+        assert!(!gutter.update(9, 0));
+        assert!(gutter.update(10, 0));
+        assert_eq!(gutter.width(), 3);
+        assert!(gutter.update(1000, 0));
+        assert_eq!(gutter.width(), 5);
+
+        gutter.numbering = Numbering::Off;
+        assert_eq!(gutter.width(), 0);
+        assert_eq!(gutter.label(3), "");
+    }
+
+    #[test]
+    fn test_labels() {
+        let mut gutter = Gutter::new(Numbering::Absolute);
+        gutter.update(12, 4);
+        assert_eq!(gutter.label(0), " 1 ");
+        assert_eq!(gutter.label(4), " 5 ");
+        assert_eq!(gutter.label(11), "12 ");
+        assert_eq!(gutter.label(12), "   ");
+
+        gutter.numbering = Numbering::Relative;
+        assert_eq!(gutter.label(0), " 4 ");
+        assert_eq!(gutter.label(4), " 0 ");
+        assert_eq!(gutter.label(11), " 7 ");
+
+        gutter.numbering = Numbering::Hybrid;
+        assert_eq!(gutter.label(0), " 4 ");
+        assert_eq!(gutter.label(4), " 5 ");
+    }
+
+    #[test]
+    fn test_relative_numbers_follow_the_cursor() {
+        let mut gutter = Gutter::new(Numbering::Absolute);
+        gutter.update(5, 0);
+        assert!(!gutter.update(5, 3));
+        gutter.numbering = Numbering::Hybrid;
+        assert!(gutter.update(5, 2));
+        assert!(!gutter.update(5, 2));
+    }
+}
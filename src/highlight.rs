@@ -0,0 +1,748 @@
+//! Syntax highlighting.
+//!
+//! A line is cut into spans, each one a token the renderer picks a colour for.
+//! Columns are in chars. Whitespace and anything we don't recognise isn't part
+//! of any span. What the tokens look like comes from the buffer's Language.
+//!
+//! Block comments and strings can go on for several lines, so lexing a line
+//! starts from the State the previous one ended in. LineStates keeps the state
+//! every line starts in, and after an edit lexes again from the changed line
+//! only until a line ends up starting in the same state as before.
+
+use language::Language;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Token {
+    Ident,
+    Keyword,
+    Type,
+    Number,
+    String,
+    Char,
+    Lifetime,
+    Comment,
+    Attribute,
+    // the name and the `!`
+    Macro,
+    Symbol,
+}
+
+/// what a line leaves open for the next one
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum State {
+    Normal,
+    // block comments may nest
+    BlockComment(usize),
+    // opened with the language's string quote number n
+    String(usize),
+    // and with its block string quote number n
+    BlockString(usize),
+    // with this many #
+    RawString(usize),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub token: Token,
+}
+
+struct Lexer<'a> {
+    language: &'a Language,
+    line: &'a str,
+    characters: Vec<char>,
+    // byte offset of each char in the line
+    offsets: Vec<usize>,
+    position: usize,
+    // what's still open where we are
+    state: State,
+}
+
+impl<'a> Lexer<'a> {
+    fn peek(&self, ahead: usize) -> Option<char> {
+        self.characters.get(self.position + ahead).cloned()
+    }
+
+    fn at(&self, text: &str) -> bool {
+        text.chars().enumerate().all(|(i, character)| self.peek(i) == Some(character))
+    }
+
+    // the first of `starts` we're at
+    fn at_any(&self, starts: &'a [String]) -> Option<&'a str> {
+        starts.iter().find(|start| self.at(start)).map(|start| &start[..])
+    }
+
+    fn skip(&mut self, text: &str) {
+        self.position += text.chars().count();
+    }
+
+    fn skip_while<F: Fn(char) -> bool>(&mut self, predicate: F) {
+        while self.peek(0).is_some_and(&predicate) {
+            self.position += 1;
+        }
+    }
+
+    // Reads up to and including an unescaped `quote`, or to the end of the
+    // line if there's none. Returns whether the quote was found.
+    fn quoted(&mut self, quote: &str) -> bool {
+        while self.position < self.characters.len() {
+            if self.at(quote) {
+                self.skip(quote);
+                return true;
+            }
+            if self.characters[self.position] == '\\' {
+                self.position += 1;
+            }
+            self.position += 1;
+        }
+        self.position = self.position.min(self.characters.len());
+        false
+    }
+
+    // the rest of a string, which may go on in the next line
+    fn string_body(&mut self, index: usize) {
+        let quote = self.language.strings[index].to_string();
+        if !self.quoted(&quote) && self.language.multiline_strings {
+            self.state = State::String(index);
+        }
+    }
+
+    fn block_string_body(&mut self, index: usize) {
+        let language = self.language;
+        if !self.quoted(&language.block_strings[index]) {
+            self.state = State::BlockString(index);
+        }
+    }
+
+    // At r"..." or r#"..."#, maybe with a b in front. The hashes alone don't
+    // start one, it's still being typed or it's r followed by symbols.
+    fn at_raw_string(&self) -> bool {
+        let start = if self.peek(0) == Some('b') { 1 } else { 0 };
+        if self.peek(start) != Some('r') {
+            return false;
+        }
+        let mut ahead = start + 1;
+        while self.peek(ahead) == Some('#') {
+            ahead += 1;
+        }
+        self.peek(ahead) == Some('"')
+    }
+
+    // r"..." and r#"..."#, starting at the r
+    fn raw_string(&mut self) {
+        self.position += 1;
+        let mut hashes = 0;
+        while self.peek(0) == Some('#') {
+            hashes += 1;
+            self.position += 1;
+        }
+        self.position += 1;
+        self.raw_string_body(hashes);
+    }
+
+    fn raw_string_body(&mut self, hashes: usize) {
+        let closing: String = Some('"').into_iter().chain((0..hashes).map(|_| '#')).collect();
+        while self.position < self.characters.len() && !self.at(&closing) {
+            self.position += 1;
+        }
+        if self.position < self.characters.len() {
+            self.position += closing.len();
+        } else {
+            self.state = State::RawString(hashes);
+        }
+    }
+
+    // the rest of a comment `depth` levels deep
+    fn block_comment(&mut self, mut depth: usize) {
+        let (start, end) = match self.language.block_comment {
+            Some((ref start, ref end)) => (start, end),
+            None => return,
+        };
+        while self.position < self.characters.len() {
+            if self.at(end) {
+                self.skip(end);
+                depth -= 1;
+                if depth == 0 {
+                    return;
+                }
+            } else if self.language.nested_comments && self.at(start) {
+                self.skip(start);
+                depth += 1;
+            } else {
+                self.position += 1;
+            }
+        }
+        self.state = State::BlockComment(depth);
+    }
+
+    // After a `'`, either a char literal or a lifetime (or a label, which
+    // looks the same).
+    fn quote(&mut self) -> Option<Token> {
+        self.position += 1;
+        if self.char_literal() {
+            Some(Token::Char)
+        } else if self.language.lifetimes && self.peek(0).is_some_and(is_ident_start) {
+            self.skip_while(is_ident_continue);
+            Some(Token::Lifetime)
+        } else {
+            self.symbol('\'')
+        }
+    }
+    // Reads what's left of a char literal after its opening quote: one char
+    // or an escape, and the closing quote. Moves on only if that's what's
+    // there, so 'a is left for a lifetime.
+    fn char_literal(&mut self) -> bool {
+        let start = self.position;
+        match self.peek(0) {
+            Some('\\') => {
+                self.position += 1;
+                match self.peek(0) {
+                    Some('u') if self.peek(1) == Some('{') => {
+                        self.position += 2;
+                        self.skip_while(|character| character.is_ascii_hexdigit() || character == '_');
+                        if self.peek(0) == Some('}') {
+                            self.position += 1;
+                        }
+                    },
+                    Some('x') => {
+                        self.position += 1;
+                        for _ in 0..2 {
+                            if self.peek(0).is_some_and(|character| character.is_ascii_hexdigit()) {
+                                self.position += 1;
+                            }
+                        }
+                    },
+                    Some(_) => self.position += 1,
+                    None => {},
+                }
+            },
+            Some('\'') | None => return false,
+            Some(_) => self.position += 1,
+        }
+        if self.peek(0) == Some('\'') {
+            self.position += 1;
+            true
+        } else {
+            self.position = start;
+            false
+        }
+    }
+
+    // as long as the language's number pattern matches, at least a digit
+    fn number(&mut self) {
+        let rest = &self.line[self.offsets[self.position]..];
+        let length = match self.language.number {
+            Some(ref number) => number.find(rest).map_or(0, |found| found.as_str().chars().count()),
+            None => 0,
+        };
+        self.position += length.max(1);
+    }
+
+    fn word(&mut self) -> Token {
+        let language = self.language;
+        let start = self.position;
+        // raw identifiers
+        if language.raw_strings && self.at("r#") && self.peek(2).is_some_and(is_ident_start) {
+            self.position += 2;
+            self.skip_while(is_ident_continue);
+            return Token::Ident;
+        }
+        self.skip_while(is_ident_continue);
+        let word: String = self.characters[start..self.position].iter().collect();
+        if language.macros && self.peek(0) == Some('!') && self.peek(1) != Some('=') {
+            self.position += 1;
+            Token::Macro
+        } else if language.keywords.contains(&word) {
+            Token::Keyword
+        } else if language.types.contains(&word) {
+            Token::Type
+        } else {
+            Token::Ident
+        }
+    }
+
+    // #[...] and #![...], brackets inside are matched
+    fn attribute(&mut self) {
+        let mut depth = 0;
+        while let Some(character) = self.peek(0) {
+            self.position += 1;
+            match character {
+                '[' => depth += 1,
+                ']' => {
+                    depth -= 1;
+                    if depth == 0 { break; }
+                },
+                '"' => { self.quoted("\""); },
+                _ => {},
+            }
+        }
+    }
+
+    // a char we've moved past, if it's one the language colours
+    fn symbol(&self, character: char) -> Option<Token> {
+        if self.language.symbols.contains(&character) { Some(Token::Symbol) } else { None }
+    }
+
+    // Starts a string if there's one here, maybe after one of the prefixes.
+    fn string(&mut self) -> Option<Token> {
+        let language = self.language;
+        let start = self.position;
+        let prefixes = Some("").into_iter().chain(language.string_prefixes.iter().map(|prefix| &prefix[..]));
+        for prefix in prefixes {
+            self.position = start;
+            if !self.at(prefix) {
+                continue;
+            }
+            self.skip(prefix);
+            if let Some(index) = language.block_strings.iter().position(|quote| self.at(quote)) {
+                self.skip(&language.block_strings[index]);
+                self.block_string_body(index);
+                return Some(Token::String);
+            }
+            let character = self.peek(0);
+            if let Some(index) = language.strings.iter().position(|&quote| Some(quote) == character) {
+                self.position += 1;
+                self.string_body(index);
+                return Some(Token::String);
+            }
+            if language.chars && !prefix.is_empty() && character == Some('\'') {
+                // b'x'
+                self.quote();
+                return Some(Token::Char);
+            }
+        }
+        self.position = start;
+        None
+    }
+
+    // None when what we moved past isn't anything to highlight
+    fn next_token(&mut self) -> Option<Token> {
+        let language = self.language;
+        let character = self.characters[self.position];
+        if self.at_any(&language.line_comments).is_some() {
+            self.position = self.characters.len();
+            return Some(Token::Comment);
+        }
+        if let Some((ref start, _)) = language.block_comment {
+            if self.at(start) {
+                self.skip(start);
+                self.block_comment(1);
+                return Some(Token::Comment);
+            }
+        }
+        if let Some(start) = self.at_any(&language.attributes) {
+            if start.ends_with('[') {
+                self.attribute();
+            } else {
+                self.skip(start);
+                self.skip_while(|character| is_ident_continue(character) || character == '.');
+            }
+            return Some(Token::Attribute);
+        }
+        if language.raw_strings && self.at_raw_string() {
+            if character == 'b' {
+                self.position += 1;
+            }
+            self.raw_string();
+            return Some(Token::String);
+        }
+        if let Some(token) = self.string() {
+            return Some(token);
+        }
+
+        if language.chars && character == '\'' {
+            self.quote()
+        } else if character.is_ascii_digit() && language.number.is_some() {
+            self.number();
+            Some(Token::Number)
+        } else if is_ident_continue(character) {
+            Some(self.word())
+        } else {
+            self.position += 1;
+            self.symbol(character)
+        }
+    }
+}
+
+fn is_ident_start(character: char) -> bool {
+    character.is_alphabetic() || character == '_'
+}
+
+fn is_ident_continue(character: char) -> bool {
+    character.is_alphanumeric() || character == '_'
+}
+
+/// The spans of a line that starts in `state`, and the state it ends in.
+pub fn lex_line(language: &Language, line: &str, state: State) -> (Vec<Span>, State) {
+    let (offsets, characters) = line.char_indices().unzip();
+    let mut lexer = Lexer {language, line, characters, offsets, position: 0, state: State::Normal};
+    let mut spans = Vec::new();
+
+    // first whatever the previous line left open
+    match state {
+        State::Normal => {},
+        State::BlockComment(depth) => lexer.block_comment(depth),
+        State::String(index) => lexer.string_body(index),
+        State::BlockString(index) => lexer.block_string_body(index),
+        State::RawString(hashes) => lexer.raw_string_body(hashes),
+    }
+    if lexer.position > 0 {
+        let token = match state {
+            State::BlockComment(_) => Token::Comment,
+            _ => Token::String,
+        };
+        spans.push(Span {start: 0, end: lexer.position, token});
+    }
+
+    // directives take the whole line, indenting them is fine
+    if state == State::Normal {
+        lexer.skip_while(char::is_whitespace);
+        if lexer.at_any(&language.directives).is_some() {
+            let start = lexer.position;
+            lexer.position = lexer.characters.len();
+            spans.push(Span {start, end: lexer.position, token: Token::Attribute});
+        }
+    }
+
+    while lexer.position < lexer.characters.len() {
+        if lexer.characters[lexer.position].is_whitespace() {
+            lexer.position += 1;
+            continue;
+        }
+        let start = lexer.position;
+        if let Some(token) = lexer.next_token() {
+            spans.push(Span {start, end: lexer.position, token});
+        }
+    }
+    (spans, lexer.state)
+}
+
+/// The state each line starts in, known for the first few lines of a buffer
+/// at least as far down as it's been shown.
+pub struct LineStates {
+    states: Vec<State>,
+}
+
+impl LineStates {
+    pub fn new() -> LineStates {
+        LineStates {states: vec![State::Normal]}
+    }
+
+    /// Lines we don't know about yet start in whatever state the last one we
+    /// know ended in, the caller should have lexed down to them first.
+    pub fn get(&self, line_number: usize) -> State {
+        self.states[line_number.min(self.states.len() - 1)]
+    }
+
+    /// Works out the states down to `line_number`, `get_line` gives the text
+    /// of a line and `line_count` is how many there are.
+    pub fn lex_to<F: Fn(usize) -> String>(&mut self, language: &Language, line_number: usize, line_count: usize,
+                                          get_line: F) {
+        while self.states.len() <= line_number && self.states.len() <= line_count {
+            let last = self.states.len() - 1;
+            let (_, state) = lex_line(language, &get_line(last), self.states[last]);
+            self.states.push(state);
+        }
+    }
+
+    /// Line `line_number` was edited, `removed` newlines in it were deleted
+    /// and `inserted` new ones added. Lexes again from there until the state
+    /// settles, that is, until a line after the edit starts in the same state
+    /// it did before. Returns the last line that may look different now.
+    pub fn edited<F: Fn(usize) -> String>(&mut self, language: &Language, line_number: usize, removed: usize,
+                                          inserted: usize, get_line: F) -> usize {
+        if line_number >= self.states.len() {
+            return line_number + inserted;
+        }
+        let tail_start = (line_number + 1 + removed).min(self.states.len());
+        let tail = self.states.split_off(tail_start);
+        self.states.truncate(line_number + 1);
+        self.states.extend((0..inserted).map(|_| State::Normal));
+        self.states.extend(tail);
+
+        // the new lines have to be lexed no matter what
+        let mut current = line_number;
+        while current + 1 < self.states.len() {
+            let (_, state) = lex_line(language, &get_line(current), self.states[current]);
+            let new_line = current < line_number + inserted;
+            if !new_line && self.states[current + 1] == state {
+                break;
+            }
+            self.states[current + 1] = state;
+            current += 1;
+        }
+        current
+    }
+}
+
+impl Default for LineStates {
+    fn default() -> LineStates {
+        LineStates::new()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use std::rc::Rc;
+    use language::{Language, Languages};
+
+    fn language(filename: &str) -> Rc<Language> {
+        Languages::built_in().detect(filename, "")
+    }
+
+    fn rust() -> Rc<Language> {
+        language("main.rs")
+    }
+
+    // the text and token of every span in a Rust line, leaving out symbols
+    fn tokens(line: &str) -> Vec<(String, Token)> {
+        tokens_in(&rust(), line)
+    }
+
+    fn tokens_in(language: &Language, line: &str) -> Vec<(String, Token)> {
+        let characters: Vec<char> = line.chars().collect();
+        lex_line(language, line, State::Normal).0.into_iter()
+            .filter(|span| span.token != Token::Symbol)
+            .map(|span| (characters[span.start..span.end].iter().collect(), span.token))
+            .collect()
+    }
+
+    fn token(text: &str, token: Token) -> (String, Token) {
+        (text.to_string(), token)
+    }
+
+    #[test]
+    fn test_spacing_doesnt_matter() {
+        let expected = vec![token("let", Token::Keyword), token("x", Token::Ident), token("5", Token::Number)];
+        assert_eq!(tokens("let x = 5;"), expected);
+        assert_eq!(tokens("let x=5;"), expected);
+        assert_eq!(tokens("fn main()"), vec![token("fn", Token::Keyword), token("main", Token::Ident)]);
+        assert_eq!(tokens("(self)"), vec![token("self", Token::Keyword)]);
+        assert_eq!(tokens("x: &mut u8"), vec![token("x", Token::Ident), token("mut", Token::Keyword),
+                                              token("u8", Token::Type)]);
+    }
+
+    #[test]
+    fn test_spans_are_char_columns() {
+        assert_eq!(lex_line(&rust(), "añ = 1", State::Normal).0, vec![
+            Span {start: 0, end: 2, token: Token::Ident},
+            Span {start: 3, end: 4, token: Token::Symbol},
+            Span {start: 5, end: 6, token: Token::Number},
+        ]);
+    }
+
+    #[test]
+    fn test_numbers() {
+        assert_eq!(tokens("1_000u32 0xff 1.5e-3 2.0f64"), vec![
+            token("1_000u32", Token::Number), token("0xff", Token::Number),
+            token("1.5e-3", Token::Number), token("2.0f64", Token::Number),
+        ]);
+        // a range isn't a decimal point, and 0x1e-2 is a subtraction
+        assert_eq!(tokens("0..10"), vec![token("0", Token::Number), token("10", Token::Number)]);
+        assert_eq!(tokens("0x1e-2"), vec![token("0x1e", Token::Number), token("2", Token::Number)]);
+        assert_eq!(tokens("x.0"), vec![token("x", Token::Ident), token("0", Token::Number)]);
+    }
+
+    #[test]
+    fn test_strings() {
+        assert_eq!(tokens(r#"let s = "a \"quoted\" word";"#), vec![
+            token("let", Token::Keyword), token("s", Token::Ident),
+            token(r#""a \"quoted\" word""#, Token::String),
+        ]);
+        assert_eq!(tokens(r#"r"C:\path" b"bytes""#), vec![
+            token(r#"r"C:\path""#, Token::String), token(r#"b"bytes""#, Token::String),
+        ]);
+        assert_eq!(tokens(r###"r#"has "quotes""# x"###), vec![
+            token(r###"r#"has "quotes""#"###, Token::String), token("x", Token::Ident),
+        ]);
+        // unfinished, goes on to the end of the line
+        assert_eq!(tokens(r#""open // not a comment"#), vec![token(r#""open // not a comment"#, Token::String)]);
+    }
+
+    #[test]
+    fn test_chars_and_lifetimes() {
+        assert_eq!(tokens("'a' '\\n' b'x'"), vec![
+            token("'a'", Token::Char), token("'\\n'", Token::Char), token("b'x'", Token::Char),
+        ]);
+        assert_eq!(tokens("fn f<'a>(x: &'a str)"), vec![
+            token("fn", Token::Keyword), token("f", Token::Ident), token("'a", Token::Lifetime),
+            token("x", Token::Ident), token("'a", Token::Lifetime), token("str", Token::Type),
+        ]);
+    }
+
+    #[test]
+    fn test_comments_attributes_and_macros() {
+        assert_eq!(tokens("x /* a */ y // rest"), vec![
+            token("x", Token::Ident), token("/* a */", Token::Comment),
+            token("y", Token::Ident), token("// rest", Token::Comment),
+        ]);
+        assert_eq!(tokens("#[derive(Debug, PartialEq)] struct"), vec![
+            token("#[derive(Debug, PartialEq)]", Token::Attribute), token("struct", Token::Keyword),
+        ]);
+        assert_eq!(tokens("#![allow(warnings)]"), vec![token("#![allow(warnings)]", Token::Attribute)]);
+        assert_eq!(tokens("println!(\"{}\", x != y)"), vec![
+            token("println!", Token::Macro), token("\"{}\"", Token::String),
+            token("x", Token::Ident), token("y", Token::Ident),
+        ]);
+        assert_eq!(tokens("r#type"), vec![token("r#type", Token::Ident)]);
+    }
+
+    #[test]
+    fn test_raw_string_starts_being_typed() {
+        // no quote yet, so not a raw string
+        for line in &["r##", "br#", "x = r#"] {
+            let spans = lex_line(&rust(), line, State::Normal).0;
+            assert!(spans.iter().all(|span| span.end <= line.len()), "{}: {:?}", line, spans);
+        }
+        assert_eq!(tokens("r##"), vec![token("r", Token::Ident)]);
+        assert_eq!(tokens("br#"), vec![token("br", Token::Ident)]);
+        assert_eq!(lex_line(&rust(), "x = r#", State::Normal).1, State::Normal);
+        assert_eq!(tokens(r##"br#"bytes"#"##), vec![token(r##"br#"bytes"#"##, Token::String)]);
+    }
+
+    #[test]
+    fn test_state_carries_over_lines() {
+        assert_eq!(lex_line(&rust(), "x /* open", State::Normal).1, State::BlockComment(1));
+        assert_eq!(lex_line(&rust(), "/* a /* nested */", State::Normal).1, State::BlockComment(1));
+        let (spans, state) = lex_line(&rust(), "still */ x", State::BlockComment(1));
+        assert_eq!(spans[0], Span {start: 0, end: 8, token: Token::Comment});
+        assert_eq!(spans[1].token, Token::Ident);
+        assert_eq!(state, State::Normal);
+
+        assert_eq!(lex_line(&rust(), "let s = \"two", State::Normal).1, State::String(0));
+        assert_eq!(lex_line(&rust(), "lines\"; x", State::String(0)).0[0],
+                   Span {start: 0, end: 6, token: Token::String});
+        assert_eq!(lex_line(&rust(), "r##\"raw \"# still", State::Normal).1, State::RawString(2));
+        assert_eq!(lex_line(&rust(), "done\"##", State::RawString(2)),
+                   (vec![Span {start: 0, end: 7, token: Token::String}], State::Normal));
+        // a line comment doesn't open anything
+        assert_eq!(lex_line(&rust(), "// \" /*", State::Normal).1, State::Normal);
+    }
+
+    #[test]
+    fn test_edits_relex_until_the_state_settles() {
+        let mut lines = vec!["a", "b", "c", "d"];
+        let rust = rust();
+        let mut states = LineStates::new();
+        states.lex_to(&rust, 10, lines.len(), |line| lines[line].to_string());
+        assert_eq!(states.get(3), State::Normal);
+
+        // opening a comment changes every line after it
+        lines[1] = "b /*";
+        assert_eq!(states.edited(&rust, 1, 0, 0, |line| lines[line].to_string()), 4);
+        assert_eq!(states.get(2), State::BlockComment(1));
+        assert_eq!(states.get(4), State::BlockComment(1));
+
+        // and closing it puts them back
+        lines[2] = "*/ c";
+        assert_eq!(states.edited(&rust, 2, 0, 0, |line| lines[line].to_string()), 4);
+        assert_eq!(states.get(3), State::Normal);
+
+        // not opening it any more only matters until it's closed
+        lines[1] = "b";
+        assert_eq!(states.edited(&rust, 1, 0, 0, |line| lines[line].to_string()), 2);
+        assert_eq!(states.get(2), State::Normal);
+
+        // a line that changes nothing stops right away
+        lines[0] = "aa";
+        assert_eq!(states.edited(&rust, 0, 0, 0, |line| lines[line].to_string()), 0);
+
+        // new lines are always lexed
+        lines.insert(2, "/* x");
+        assert_eq!(states.edited(&rust, 1, 0, 1, |line| lines[line].to_string()), 3);
+        assert_eq!(states.get(3), State::BlockComment(1));
+        assert_eq!(states.get(4), State::Normal);
+        lines.remove(2);
+        assert_eq!(states.edited(&rust, 1, 1, 0, |line| lines[line].to_string()), 2);
+        assert_eq!(states.get(3), State::Normal);
+    }
+
+    #[test]
+    fn test_lifetimes_are_not_chars() {
+        assert_eq!(tokens("fn foo<'a>(x: &'a str) -> &'static str"), vec![
+            token("fn", Token::Keyword), token("foo", Token::Ident), token("'a", Token::Lifetime),
+            token("x", Token::Ident), token("'a", Token::Lifetime), token("str", Token::Type),
+            token("'static", Token::Lifetime), token("str", Token::Type),
+        ]);
+        assert_eq!(tokens("impl<'a, 'b: 'a> T<'a, 'b>"), vec![
+            token("impl", Token::Keyword), token("'a", Token::Lifetime), token("'b", Token::Lifetime),
+            token("'a", Token::Lifetime), token("T", Token::Ident), token("'a", Token::Lifetime),
+            token("'b", Token::Lifetime),
+        ]);
+        // labels look like lifetimes
+        assert_eq!(tokens("'outer: loop { break 'outer; }"), vec![
+            token("'outer", Token::Lifetime), token("loop", Token::Keyword),
+            token("break", Token::Keyword), token("'outer", Token::Lifetime),
+        ]);
+    }
+
+    #[test]
+    fn test_char_literals() {
+        assert_eq!(tokens("'a' 'é' ' ' '\\'' '\\\\' '\\n' '\\t' '\\0'"), vec![
+            token("'a'", Token::Char), token("'é'", Token::Char), token("' '", Token::Char),
+            token("'\\''", Token::Char), token("'\\\\'", Token::Char), token("'\\n'", Token::Char),
+            token("'\\t'", Token::Char), token("'\\0'", Token::Char),
+        ]);
+        assert_eq!(tokens("'\\x7f' '\\u{1F600}' '\\u{10_FFFF}' b'\\x00' b'a'"), vec![
+            token("'\\x7f'", Token::Char), token("'\\u{1F600}'", Token::Char),
+            token("'\\u{10_FFFF}'", Token::Char), token("b'\\x00'", Token::Char), token("b'a'", Token::Char),
+        ]);
+        // a char next to a lifetime doesn't swallow it
+        assert_eq!(tokens("f::<'a>('x')"), vec![
+            token("f", Token::Ident), token("'a", Token::Lifetime), token("'x'", Token::Char),
+        ]);
+        // a broken one doesn't take the rest of the line with it
+        assert_eq!(tokens("'\\n x = 1"), vec![
+            token("n", Token::Ident), token("x", Token::Ident), token("1", Token::Number),
+        ]);
+    }
+
+    #[test]
+    fn test_other_languages() {
+        let python = language("x.py");
+        assert_eq!(tokens_in(&python, "def f(x): return 'a' + rb\"b\"  # done"), vec![
+            token("def", Token::Keyword), token("f", Token::Ident), token("x", Token::Ident),
+            token("return", Token::Keyword), token("'a'", Token::String), token("rb\"b\"", Token::String),
+            token("# done", Token::Comment),
+        ]);
+        assert_eq!(tokens_in(&python, "@property"), vec![token("@property", Token::Attribute)]);
+        // a quote left open doesn't go on, a block string does
+        assert_eq!(lex_line(&python, "s = 'open", State::Normal).1, State::Normal);
+        assert_eq!(lex_line(&python, "s = \"\"\"open", State::Normal).1, State::BlockString(0));
+        assert_eq!(lex_line(&python, "still\"\"\" 1.5j", State::BlockString(0)).0, vec![
+            Span {start: 0, end: 8, token: Token::String}, Span {start: 9, end: 13, token: Token::Number},
+        ]);
+
+        let c = language("x.c");
+        assert_eq!(tokens_in(&c, "  #include <stdio.h>"), vec![token("#include <stdio.h>", Token::Attribute)]);
+        assert_eq!(tokens_in(&c, "unsigned c = 'x'; /* a /* b */"), vec![
+            token("unsigned", Token::Type), token("c", Token::Ident), token("'x'", Token::Char),
+            token("/* a /* b */", Token::Comment),
+        ]);
+
+        let shell = language("x.sh");
+        assert_eq!(tokens_in(&shell, "if [ -f \"$x\" ]; then echo 'y'; fi # why"), vec![
+            token("if", Token::Keyword), token("f", Token::Ident), token("\"$x\"", Token::String),
+            token("then", Token::Keyword), token("echo", Token::Type), token("'y'", Token::String),
+            token("fi", Token::Keyword), token("# why", Token::Comment),
+        ]);
+
+        let markdown = language("x.md");
+        assert_eq!(tokens_in(&markdown, "## Title"), vec![token("## Title", Token::Attribute)]);
+        assert_eq!(lex_line(&markdown, "```rust", State::Normal).1, State::BlockString(0));
+
+        let toml = language("Cargo.toml");
+        assert_eq!(tokens_in(&toml, "[dependencies]"), vec![token("[dependencies]", Token::Attribute)]);
+        assert_eq!(tokens_in(&toml, "x = 1979-05-27T07:32:00Z # when"), vec![
+            token("x", Token::Ident), token("1979-05-27T07:32:00Z", Token::Number), token("# when", Token::Comment),
+        ]);
+
+        // nothing to highlight in plain text
+        let (spans, _) = lex_line(&Language::plain(), "fn x() { 'a' } // 1", State::Normal);
+        assert!(spans.iter().all(|span| span.token == Token::Ident));
+    }
+}
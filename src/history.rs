@@ -0,0 +1,139 @@
+// Undo/redo history for a Buffer.
+//
+// Every mutation of the rope is recorded as an Edit. The edits made by a
+// single command are committed together as a Change, along with where the
+// cursor was before and after it, so undoing a change is replaying the
+// inverse of its edits backwards and putting the cursor back.
+//
+// The history also remembers which change the file was last saved at, so
+// undoing back to it leaves the buffer unmodified again.
+
+use std::mem;
+
+use Cursor;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Edit {
+    Insert { at: usize, text: String },
+    Remove { at: usize, text: String },
+}
+
+impl Edit {
+    fn invert(&self) -> Edit {
+        match *self {
+            Edit::Insert { at, ref text } => Edit::Remove { at, text: text.clone() },
+            Edit::Remove { at, ref text } => Edit::Insert { at, text: text.clone() },
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ChangeKind {
+    // consecutive typed characters are merged into one change
+    Typing,
+    Other,
+}
+
+struct Change {
+    kind: ChangeKind,
+    edits: Vec<Edit>,
+    cursor_before: Cursor,
+    cursor_after: Cursor,
+}
+
+pub struct History {
+    undo_stack: Vec<Change>,
+    redo_stack: Vec<Change>,
+    // edits recorded since the last commit
+    pending: Vec<Edit>,
+    // whether the next Typing change may be merged into the last one
+    can_merge: bool,
+    // how many changes there were to undo when the file was saved, None if
+    // that state can't be reached anymore
+    saved: Option<usize>,
+}
+
+impl History {
+    pub fn new() -> History {
+        History {
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
+            pending: Vec::new(),
+            can_merge: false,
+            saved: Some(0),
+        }
+    }
+
+    pub fn record(&mut self, edit: Edit) {
+        self.pending.push(edit);
+    }
+
+    // Close the pending edits into a change. Any new change makes the redo
+    // stack meaningless, so it's dropped.
+    pub fn commit(&mut self, kind: ChangeKind, cursor_before: Cursor, cursor_after: Cursor) {
+        if self.pending.is_empty() { return; }
+        let edits = mem::take(&mut self.pending);
+        self.redo_stack.clear();
+        // the saved state was among the changes just dropped
+        if self.saved.is_some_and(|saved| saved > self.undo_stack.len()) {
+            self.saved = None;
+        }
+
+        if kind == ChangeKind::Typing && self.can_merge {
+            if let Some(last) = self.undo_stack.last_mut() {
+                if last.kind == ChangeKind::Typing && last.cursor_after == cursor_before {
+                    last.edits.extend(edits);
+                    last.cursor_after = cursor_after;
+                    return;
+                }
+            }
+        }
+
+        self.undo_stack.push(Change {kind, edits, cursor_before, cursor_after});
+        self.can_merge = kind == ChangeKind::Typing;
+    }
+
+    // The edits that revert the last change, in the order they have to be
+    // applied, and where the cursor should go.
+    pub fn undo(&mut self) -> Option<(Vec<Edit>, Cursor)> {
+        let change = self.undo_stack.pop()?;
+        let edits = change.edits.iter().rev().map(Edit::invert).collect();
+        let cursor = change.cursor_before;
+        self.redo_stack.push(change);
+        self.can_merge = false;
+        Some((edits, cursor))
+    }
+
+    // The edits that reapply the last undone change, and where the cursor
+    // should go.
+    pub fn redo(&mut self) -> Option<(Vec<Edit>, Cursor)> {
+        let change = self.redo_stack.pop()?;
+        let edits = change.edits.clone();
+        let cursor = change.cursor_after;
+        self.undo_stack.push(change);
+        self.can_merge = false;
+        Some((edits, cursor))
+    }
+
+    // The text is saved as it is now. Typing after this starts a new change,
+    // or undoing it would skip past the saved state.
+    pub fn mark_saved(&mut self) {
+        self.saved = Some(self.undo_stack.len());
+        self.can_merge = false;
+    }
+
+    // for changes the history can't undo, like the line endings
+    pub fn forget_saved(&mut self) {
+        self.saved = None;
+    }
+
+    pub fn is_saved(&self) -> bool {
+        self.pending.is_empty() && self.saved == Some(self.undo_stack.len())
+    }
+}
+
+impl Default for History {
+    fn default() -> History {
+        History::new()
+    }
+}
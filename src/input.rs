@@ -0,0 +1,199 @@
+// Keyboard input on top of the backend, and the terminal changing size.
+//
+// termbox doesn't know about modifiers on arrow keys. Terminals send
+// shift+arrow as an xterm sequence ("\x1b[1;2A"), which termbox hands us as
+// an Esc followed by the rest of the sequence as separate chars, so we put it
+// back together here. Sequences for keys we don't know are dropped rather
+// than typed as text.
+
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use backend::{Backend, Event, Key};
+
+// How long to wait for each char of an escape sequence. The terminal sends
+// it all at once, but over a slow connection it can arrive in pieces.
+const SEQUENCE_TIMEOUT: Duration = Duration::from_millis(15);
+
+// we give up on a sequence that goes on longer than this
+const MAX_SEQUENCE_LENGTH: usize = 16;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Input {
+    Key(Key),
+    // shift + an arrow key
+    Shift(Key),
+    // the screen is a different size now, the backend knows which
+    Resize,
+}
+
+pub struct InputReader {
+    // what we read while looking for a sequence that turned out not to be one
+    pending: VecDeque<Event>,
+}
+
+impl InputReader {
+    pub fn new() -> InputReader {
+        InputReader {pending: VecDeque::new()}
+    }
+
+    // Waits for the next input, or for `timeout` if there's one.
+    pub fn poll(&mut self, backend: &dyn Backend, timeout: Option<Duration>) -> Option<Input> {
+        let event = match self.pending.pop_front() {
+            Some(event) => event,
+            None => backend.poll_event(timeout),
+        };
+        let key = match event {
+            Event::Key(key) => key,
+            Event::Resize(_, _) => return Some(Input::Resize),
+            Event::None => return None,
+        };
+        if key != Key::Esc || !self.pending.is_empty() {
+            return Some(Input::Key(key));
+        }
+
+        // the rest of an escape sequence follows right away, a lone Esc
+        // isn't followed by anything
+        let mut sequence = Vec::new();
+        let mut interrupted = None;
+        while sequence.len() < MAX_SEQUENCE_LENGTH && !sequence_ended(&sequence) {
+            match backend.poll_event(Some(SEQUENCE_TIMEOUT)) {
+                Event::Key(key) => sequence.push(key),
+                Event::None => break,
+                // not part of it, but not to be lost either
+                event => {
+                    interrupted = Some(event);
+                    break;
+                },
+            }
+        }
+
+        let input = match decode_sequence(&sequence) {
+            Some(input) => Some(input),
+            // a key we don't know about, its chars aren't meant as text
+            None if is_csi(&sequence) => None,
+            None => {
+                self.pending.extend(sequence.into_iter().map(Event::Key));
+                Some(Input::Key(Key::Esc))
+            },
+        };
+        self.pending.extend(interrupted);
+        input
+    }
+}
+
+impl Default for InputReader {
+    fn default() -> InputReader {
+        InputReader::new()
+    }
+}
+
+// The final char of a CSI sequence, after the parameters.
+fn is_final(character: char) -> bool {
+    ('\x40'..='\x7e').contains(&character)
+}
+
+// Whether what came after an Esc can't go on: it's a whole CSI sequence
+// ("[", parameters, then a final char), or it isn't one at all.
+fn sequence_ended(sequence: &[Key]) -> bool {
+    match sequence.split_first() {
+        None => false,
+        Some((&Key::Char('['), rest)) => match rest.last() {
+            None => false,
+            Some(&Key::Char(character)) => is_final(character) || !('\x20'..'\x40').contains(&character),
+            Some(_) => true,
+        },
+        Some(_) => true,
+    }
+}
+
+// whether what came after an Esc is a whole CSI sequence
+fn is_csi(sequence: &[Key]) -> bool {
+    match (sequence.first(), sequence.last()) {
+        (Some(&Key::Char('[')), Some(&Key::Char(last))) => sequence.len() > 1 && is_final(last),
+        _ => false,
+    }
+}
+
+// what came after an Esc, if it's something we know
+fn decode_sequence(sequence: &[Key]) -> Option<Input> {
+    let text: String = sequence.iter().map(|key| match *key {
+        Key::Char(character) => character,
+        _ => '\0',
+    }).collect();
+
+    match &text[..] {
+        "[1;2A" => Some(Input::Shift(Key::Up)),
+        "[1;2B" => Some(Input::Shift(Key::Down)),
+        "[1;2C" => Some(Input::Shift(Key::Right)),
+        "[1;2D" => Some(Input::Shift(Key::Left)),
+        _ => None,
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use grid::Grid;
+    use theme::{Class, Palette, Theme};
+
+    fn keys(text: &str) -> Vec<Key> {
+        text.chars().map(Key::Char).collect()
+    }
+
+    #[test]
+    fn test_decode_shift_arrows() {
+        assert_eq!(decode_sequence(&keys("[1;2A")), Some(Input::Shift(Key::Up)));
+        assert_eq!(decode_sequence(&keys("[1;2B")), Some(Input::Shift(Key::Down)));
+        assert_eq!(decode_sequence(&keys("[1;2C")), Some(Input::Shift(Key::Right)));
+        assert_eq!(decode_sequence(&keys("[1;2D")), Some(Input::Shift(Key::Left)));
+    }
+
+    #[test]
+    fn test_decode_unknown_sequences() {
+        assert_eq!(decode_sequence(&[]), None);
+        assert_eq!(decode_sequence(&keys("[1;5A")), None);
+        assert_eq!(decode_sequence(&keys("hello")), None);
+        assert_eq!(decode_sequence(&[Key::Char('['), Key::Enter]), None);
+    }
+
+    #[test]
+    fn test_sequence_ends() {
+        assert!(!sequence_ended(&[]));
+        assert!(!sequence_ended(&keys("[")));
+        assert!(!sequence_ended(&keys("[1;5")));
+        assert!(sequence_ended(&keys("[1;5A")));
+        assert!(sequence_ended(&keys("[200~")));
+        // not a CSI sequence, or not one anymore
+        assert!(sequence_ended(&keys("x")));
+        assert!(sequence_ended(&[Key::Char('['), Key::Enter]));
+
+        assert!(is_csi(&keys("[1;5A")));
+        assert!(!is_csi(&keys("[1;5")));
+        assert!(!is_csi(&keys("[")));
+        assert!(!is_csi(&keys("x")));
+    }
+
+    #[test]
+    fn test_unknown_sequences_are_dropped() {
+        let theme = Theme::built_in("dark", Palette::Basic).unwrap();
+        let grid = Grid::new(10, 2, theme.face(Class::Text));
+        let mut input = InputReader::new();
+        grid.push_key(Key::Esc);
+        grid.type_text("[1;5Ax");
+        assert_eq!(input.poll(&grid, None), None);
+        assert_eq!(input.poll(&grid, None), Some(Input::Key(Key::Char('x'))));
+
+        // an Esc on its own, and one followed by a sequence that never ends
+        grid.push_key(Key::Esc);
+        assert_eq!(input.poll(&grid, None), Some(Input::Key(Key::Esc)));
+        grid.push_key(Key::Esc);
+        grid.type_text("[1");
+        assert_eq!(input.poll(&grid, None), Some(Input::Key(Key::Esc)));
+        assert_eq!(input.poll(&grid, None), Some(Input::Key(Key::Char('['))));
+        assert_eq!(input.poll(&grid, None), Some(Input::Key(Key::Char('1'))));
+        assert_eq!(input.poll(&grid, None), None);
+    }
+}
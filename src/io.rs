@@ -0,0 +1,38 @@
+//! Reading and writing files.
+//!
+//! Files are saved through a temporary file renamed over the original (see
+//! src/file.rs), so a failed save leaves them as they were.
+
+use std::fs::OpenOptions;
+use std::io::{self, Read};
+use std::path::Path;
+
+use buffer::Buffer;
+use error::Error;
+use file;
+
+/// Writes the buffer to `path`, with its line endings.
+pub fn save_buffer(path: &Path, buffer: &Buffer) -> io::Result<()> {
+    file::save(path, |file| buffer.write_to(file))
+}
+
+/// The text in `path`, or None if there's no such file yet.
+pub fn read_file(path: &Path) -> Result<Option<String>, Error> {
+    let mut file_contents = String::new();
+    let read = OpenOptions::new().read(true).open(path)
+        .and_then(|mut file| file.read_to_string(&mut file_contents));
+    match read {
+        Ok(_) => Ok(Some(file_contents)),
+        Err(ref error) if error.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(error) => Err(Error::Read(path.to_string_lossy().into_owned(), error)),
+    }
+}
+
+/// A buffer with the text in `path`, or an empty one if there's no such
+/// file yet.
+pub fn open(path: &Path) -> Result<Buffer, Error> {
+    Ok(match read_file(path)? {
+        Some(text) => Buffer::from_string(&text),
+        None => Buffer::new(),
+    })
+}
@@ -0,0 +1,291 @@
+//! Language definitions.
+//!
+//! What the highlighter knows about a language comes from a small TOML file:
+//! its keywords and types, how comments and strings look, and a regex for
+//! numbers. The definitions in syntax/ are built in, and files in
+//! $XDG_CONFIG_HOME/editor2/syntax/ (~/.config/editor2/syntax/ by default) add
+//! more, or replace a built-in one with the same name. A buffer's language is
+//! picked by its file's extension, or by the interpreter in a #! line.
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use regex::Regex;
+
+use config::{self, Value};
+
+const BUILT_IN: [(&str, &str); 6] = [
+    ("rust.toml", include_str!("../syntax/rust.toml")),
+    ("toml.toml", include_str!("../syntax/toml.toml")),
+    ("markdown.toml", include_str!("../syntax/markdown.toml")),
+    ("python.toml", include_str!("../syntax/python.toml")),
+    ("c.toml", include_str!("../syntax/c.toml")),
+    ("shell.toml", include_str!("../syntax/shell.toml")),
+];
+
+#[derive(Default)]
+pub struct Language {
+    pub name: String,
+    extensions: Vec<String>,
+    // interpreters in a #! line, like "python3"
+    shebangs: Vec<String>,
+    pub keywords: HashSet<String>,
+    pub types: HashSet<String>,
+    /// punctuation worth a colour, anything else is left alone
+    pub symbols: Vec<char>,
+    pub line_comments: Vec<String>,
+    /// start and end
+    pub block_comment: Option<(String, String)>,
+    pub nested_comments: bool,
+    /// quotes around strings, escaped with a backslash
+    pub strings: Vec<char>,
+    /// what can come right before a quote and still be part of the string,
+    /// like the b in b"bytes"
+    pub string_prefixes: Vec<String>,
+    /// whether a string left open goes on in the next line
+    pub multiline_strings: bool,
+    /// quotes like """ that always go on until they're closed
+    pub block_strings: Vec<String>,
+    /// Rust's r#"..."# strings and r#ident identifiers
+    pub raw_strings: bool,
+    /// 'c' is a char, not a string
+    pub chars: bool,
+    /// 'a is a lifetime
+    pub lifetimes: bool,
+    /// name! is a macro
+    pub macros: bool,
+    /// Starts of attributes. When one ends in [ the attribute goes on until
+    /// the matching ], otherwise it's followed by a name.
+    pub attributes: Vec<String>,
+    /// starts of lines that are highlighted whole, like #include or a
+    /// Markdown heading
+    pub directives: Vec<String>,
+    pub number: Option<Regex>,
+}
+
+impl Language {
+    /// no highlighting at all, for files we don't know
+    pub fn plain() -> Language {
+        Language {name: "Text".to_string(), ..Default::default()}
+    }
+
+    pub fn from_definition(definition: &str) -> Result<Language, String> {
+        let mut language = Language::default();
+        for (line, key, value) in config::parse(definition)? {
+            let error = |message: &str| format!("line {}: {}", line, message);
+            match (&key[..], value) {
+                ("name", Value::Text(name)) => language.name = name,
+                ("extensions", Value::List(list)) => language.extensions = list,
+                ("shebangs", Value::List(list)) => language.shebangs = list,
+                ("keywords", Value::List(list)) => language.keywords = list.into_iter().collect(),
+                ("types", Value::List(list)) => language.types = list.into_iter().collect(),
+                ("symbols", Value::Text(symbols)) => language.symbols = symbols.chars().collect(),
+                ("line_comments", Value::List(list)) => language.line_comments = list,
+                ("block_comment", Value::List(mut list)) => {
+                    if list.len() != 2 {
+                        return Err(error("block_comment needs a start and an end"));
+                    }
+                    let end = list.pop().unwrap();
+                    language.block_comment = Some((list.pop().unwrap(), end));
+                },
+                ("nested_comments", Value::Bool(value)) => language.nested_comments = value,
+                ("strings", Value::List(list)) => {
+                    for quote in list {
+                        let mut characters = quote.chars();
+                        match (characters.next(), characters.next()) {
+                            (Some(character), None) => language.strings.push(character),
+                            _ => return Err(error("strings are quoted with a single char")),
+                        }
+                    }
+                },
+                ("string_prefixes", Value::List(list)) => language.string_prefixes = list,
+                ("multiline_strings", Value::Bool(value)) => language.multiline_strings = value,
+                ("block_strings", Value::List(list)) => language.block_strings = list,
+                ("raw_strings", Value::Bool(value)) => language.raw_strings = value,
+                ("chars", Value::Bool(value)) => language.chars = value,
+                ("lifetimes", Value::Bool(value)) => language.lifetimes = value,
+                ("macros", Value::Bool(value)) => language.macros = value,
+                ("attributes", Value::List(list)) => language.attributes = list,
+                ("directives", Value::List(list)) => language.directives = list,
+                ("number", Value::Text(pattern)) => {
+                    // only matches right where a number starts
+                    match Regex::new(&format!("^(?:{})", pattern)) {
+                        Ok(regex) => language.number = Some(regex),
+                        Err(_) => return Err(error("invalid number regex")),
+                    }
+                },
+                (key, _) => return Err(error(&format!("unknown key or wrong type for {}", key))),
+            }
+        }
+        if language.name.is_empty() {
+            return Err("missing name".to_string());
+        }
+        // empty ones would match everywhere
+        let empty = |list: &[String]| list.iter().any(String::is_empty);
+        if empty(&language.line_comments) || empty(&language.block_strings) || empty(&language.attributes)
+            || empty(&language.directives) || empty(&language.string_prefixes) {
+            return Err("empty delimiter".to_string());
+        }
+        if let Some((ref start, ref end)) = language.block_comment {
+            if start.is_empty() || end.is_empty() {
+                return Err("empty delimiter".to_string());
+            }
+        }
+        Ok(language)
+    }
+}
+
+pub struct Languages {
+    languages: Vec<Rc<Language>>,
+}
+
+impl Languages {
+    pub fn built_in() -> Languages {
+        let mut languages = Languages {languages: Vec::new()};
+        for &(file, definition) in BUILT_IN.iter() {
+            match Language::from_definition(definition) {
+                Ok(language) => languages.add(language),
+                Err(error) => panic!("syntax/{}: {}", file, error),
+            }
+        }
+        languages
+    }
+
+    /// Adds the user's definitions, returning what's wrong with the ones
+    /// that can't be loaded.
+    pub fn load_user(&mut self) -> Vec<String> {
+        match config::config_dir() {
+            Some(dir) => self.load_dir(&dir.join("syntax")),
+            None => Vec::new(),
+        }
+    }
+
+    fn load_dir(&mut self, dir: &Path) -> Vec<String> {
+        let mut paths: Vec<PathBuf> = match fs::read_dir(dir) {
+            Ok(entries) => entries.filter_map(|entry| entry.ok()).map(|entry| entry.path()).collect(),
+            Err(_) => return Vec::new(),
+        };
+        paths.retain(|path| path.extension().is_some_and(|extension| extension == "toml"));
+        paths.sort();
+
+        let mut errors = Vec::new();
+        for path in paths {
+            let loaded = fs::read_to_string(&path).map_err(|error| error.to_string())
+                .and_then(|definition| Language::from_definition(&definition));
+            match loaded {
+                Ok(language) => self.add(language),
+                Err(error) => errors.push(format!("Couldn't load {}: {}", path.display(), error)),
+            }
+        }
+        errors
+    }
+
+    // a language with the same name as one we have replaces it
+    fn add(&mut self, language: Language) {
+        let language = Rc::new(language);
+        match self.languages.iter().position(|known| known.name.eq_ignore_ascii_case(&language.name)) {
+            Some(index) => self.languages[index] = language,
+            None => self.languages.push(language),
+        }
+    }
+
+    /// The language of a file, by its extension or else by the interpreter
+    /// its first line asks for.
+    pub fn detect(&self, filename: &str, first_line: &str) -> Rc<Language> {
+        let extension = Path::new(filename).extension().map(|extension| extension.to_string_lossy());
+        if let Some(extension) = extension {
+            let found = self.languages.iter()
+                .find(|language| language.extensions.iter().any(|known| *known == extension));
+            if let Some(language) = found {
+                return language.clone();
+            }
+        }
+        if let Some(interpreter) = interpreter(first_line) {
+            // python3.11 is python
+            let unversioned = interpreter.trim_end_matches(|character: char| {
+                character.is_ascii_digit() || character == '.'
+            });
+            let found = self.languages.iter().find(|language| {
+                language.shebangs.iter().any(|known| *known == interpreter || *known == unversioned)
+            });
+            if let Some(language) = found {
+                return language.clone();
+            }
+        }
+        Rc::new(Language::plain())
+    }
+}
+
+// the program in a #! line, looking past /usr/bin/env
+fn interpreter(first_line: &str) -> Option<&str> {
+    if !first_line.starts_with("#!") {
+        return None;
+    }
+    let mut words = first_line[2..].split_whitespace();
+    let program = words.next()?.rsplit('/').next()?;
+    if program == "env" {
+        words.find(|word| !word.starts_with('-') && !word.contains('='))
+    } else {
+        Some(program)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_parse_definitions() {
+        let language = Language::from_definition(r#"
+            # a comment
+            name = "Test"   # another
+            keywords = [
+                "if", 'else',  # literal strings too
+                "a\"b",
+            ]
+            block_comment = ["(*", "*)"]
+            strings = ['"']
+            chars = true
+            number = '[0-9]+\.?'
+        "#).unwrap();
+        assert_eq!(language.name, "Test");
+        assert!(language.keywords.contains("else"));
+        assert!(language.keywords.contains("a\"b"));
+        assert_eq!(language.block_comment, Some(("(*".to_string(), "*)".to_string())));
+        assert_eq!(language.strings, vec!['"']);
+        assert!(language.chars && !language.lifetimes);
+        assert!(language.number.unwrap().is_match("12."));
+    }
+
+    #[test]
+    fn test_definition_errors() {
+        let error = |definition: &str| Language::from_definition(definition).err().unwrap();
+        assert_eq!(error("name = \"X\"\nkeywords = \"if\""), "line 2: unknown key or wrong type for keywords");
+        assert_eq!(error("name = \"X\"\n\nstrings = [\"ab\"]"), "line 3: strings are quoted with a single char");
+        assert_eq!(error("name = \"X\"\nkeywords = [\"if\"\n\"else\"]"), "line 3: expected , or ]");
+        assert_eq!(error("name = \"open"), "line 1: unfinished string");
+        assert_eq!(error("name = \"X\" y"), "line 1: expected the end of the line");
+        assert_eq!(error("chars = true"), "missing name");
+    }
+
+    #[test]
+    fn test_detect_by_extension_or_shebang() {
+        let languages = Languages::built_in();
+        let detect = |filename: &str, first_line: &str| languages.detect(filename, first_line).name.clone();
+        assert_eq!(detect("src/main.rs", ""), "Rust");
+        assert_eq!(detect("Cargo.toml", "[package]"), "TOML");
+        assert_eq!(detect("README.md", ""), "Markdown");
+        assert_eq!(detect("x.h", ""), "C");
+        assert_eq!(detect("script", "#!/usr/bin/env python3"), "Python");
+        assert_eq!(detect("script", "#!/usr/bin/python3.11 -u"), "Python");
+        assert_eq!(detect("configure", "#!/bin/sh"), "Shell");
+        assert_eq!(detect("run", "#!/usr/bin/env -S bash -e"), "Shell");
+        // the extension wins
+        assert_eq!(detect("build.py", "#!/bin/sh"), "Python");
+        assert_eq!(detect("notes.txt", ""), "Text");
+        assert_eq!(detect("script", "#!/usr/bin/perl"), "Text");
+    }
+}
@@ -0,0 +1,312 @@
+// How the screen is shared between views.
+//
+// A Layout is a tree. A leaf is a view taking the whole area it's given, a
+// split shares its area between two layouts, one above the other or side by
+// side. Side by side ones get a column between them for a separator.
+
+use std::mem;
+
+use backend::Key;
+
+// the smallest a view gets along a split: a row of text and the status bar,
+// or two columns
+const MIN_SIZE: usize = 2;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rect {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Rect {
+    pub fn new(x: usize, y: usize, width: usize, height: usize) -> Rect {
+        Rect {x, y, width, height}
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Orientation {
+    // one above the other
+    Stacked,
+    SideBySide,
+}
+
+pub enum Layout {
+    View(usize),
+    Split {
+        orientation: Orientation,
+        // the part of the area the first one gets, so it keeps its share
+        // when the screen changes size
+        ratio: f64,
+        first: Box<Layout>,
+        second: Box<Layout>,
+    },
+}
+
+impl Layout {
+    // Where each view goes in `area`, and where the separators go.
+    pub fn arrange(&self, area: Rect) -> (Vec<(usize, Rect)>, Vec<Rect>) {
+        let mut views = Vec::new();
+        let mut separators = Vec::new();
+        self.arrange_into(area, &mut views, &mut separators);
+        (views, separators)
+    }
+
+    fn arrange_into(&self, area: Rect, views: &mut Vec<(usize, Rect)>, separators: &mut Vec<Rect>) {
+        match *self {
+            Layout::View(view) => views.push((view, area)),
+            Layout::Split {orientation, ratio, ref first, ref second} => {
+                let (first_area, second_area, separator) = halves(orientation, ratio, area);
+                first.arrange_into(first_area, views, separators);
+                separators.extend(separator);
+                second.arrange_into(second_area, views, separators);
+            },
+        }
+    }
+
+    fn is_view(&self, view: usize) -> bool {
+        match *self {
+            Layout::View(id) => id == view,
+            Layout::Split {..} => false,
+        }
+    }
+
+    fn contains(&self, view: usize) -> bool {
+        match *self {
+            Layout::View(id) => id == view,
+            Layout::Split {ref first, ref second, ..} => first.contains(view) || second.contains(view),
+        }
+    }
+
+    // Splits the area of `view`, in `area`, in two, with `new` in the second
+    // half. Returns false if there's no room for both.
+    pub fn split(&mut self, view: usize, new: usize, orientation: Orientation, area: Rect) -> bool {
+        match *self {
+            Layout::View(id) if id == view => {
+                let available = match orientation {
+                    Orientation::Stacked    => area.height,
+                    Orientation::SideBySide => area.width.saturating_sub(1),
+                };
+                if available < 2 * MIN_SIZE {
+                    return false;
+                }
+                *self = Layout::Split {
+                    orientation,
+                    ratio: 0.5,
+                    first: Box::new(Layout::View(view)),
+                    second: Box::new(Layout::View(new)),
+                };
+                true
+            },
+            Layout::View(_) => false,
+            Layout::Split {orientation: split, ratio, ref mut first, ref mut second} => {
+                let (first_area, second_area, _) = halves(split, ratio, area);
+                first.split(view, new, orientation, first_area) || second.split(view, new, orientation, second_area)
+            },
+        }
+    }
+
+    // Takes `view` out, the one it shared its area with gets all of it.
+    // Views after it move down one to fill its place. Returns false if it's
+    // the only one.
+    pub fn remove(&mut self, view: usize) -> bool {
+        if !self.take_out(view) {
+            return false;
+        }
+        self.renumber(view);
+        true
+    }
+
+    fn take_out(&mut self, view: usize) -> bool {
+        let kept = match *self {
+            Layout::View(_) => return false,
+            Layout::Split {ref mut first, ref mut second, ..} => {
+                if first.is_view(view) {
+                    mem::replace(&mut **second, Layout::View(0))
+                } else if second.is_view(view) {
+                    mem::replace(&mut **first, Layout::View(0))
+                } else {
+                    return first.take_out(view) || second.take_out(view);
+                }
+            },
+        };
+        *self = kept;
+        true
+    }
+
+    fn renumber(&mut self, removed: usize) {
+        match *self {
+            Layout::View(ref mut id) => if *id > removed { *id -= 1 },
+            Layout::Split {ref mut first, ref mut second, ..} => {
+                first.renumber(removed);
+                second.renumber(removed);
+            },
+        }
+    }
+
+    // Makes `view` `amount` rows or columns bigger, or smaller if it's
+    // negative, along the innermost split it's in. Returns false if it
+    // isn't in one.
+    pub fn resize(&mut self, view: usize, amount: isize, area: Rect) -> bool {
+        let (orientation, ratio, first, second) = match *self {
+            Layout::View(_) => return false,
+            Layout::Split {orientation, ref mut ratio, ref mut first, ref mut second} => {
+                (orientation, ratio, first, second)
+            },
+        };
+        let (first_area, second_area, _) = halves(orientation, *ratio, area);
+        let in_first = first.contains(view);
+        if !in_first && !second.contains(view) {
+            return false;
+        }
+        let inner = if in_first {
+            first.resize(view, amount, first_area)
+        } else {
+            second.resize(view, amount, second_area)
+        };
+        if inner {
+            return true;
+        }
+
+        let (available, size) = sizes(orientation, *ratio, area);
+        let amount = if in_first { amount } else { -amount };
+        let size = (size as isize + amount).max(0) as usize;
+        *ratio = clamp_size(size, available) as f64 / available.max(1) as f64;
+        true
+    }
+}
+
+// Room along a split for both halves, and how much of it the first one gets.
+fn sizes(orientation: Orientation, ratio: f64, area: Rect) -> (usize, usize) {
+    let available = match orientation {
+        Orientation::Stacked    => area.height,
+        Orientation::SideBySide => area.width.saturating_sub(1),
+    };
+    (available, clamp_size((ratio * available as f64).round() as usize, available))
+}
+
+// leaves both halves at least MIN_SIZE, when there's room for that
+fn clamp_size(size: usize, available: usize) -> usize {
+    if available < 2 * MIN_SIZE {
+        available / 2
+    } else {
+        size.max(MIN_SIZE).min(available - MIN_SIZE)
+    }
+}
+
+// the areas of both halves of a split, and the separator between them
+fn halves(orientation: Orientation, ratio: f64, area: Rect) -> (Rect, Rect, Option<Rect>) {
+    let (available, size) = sizes(orientation, ratio, area);
+    match orientation {
+        Orientation::Stacked    => (
+            Rect::new(area.x, area.y, area.width, size),
+            Rect::new(area.x, area.y + size, area.width, available - size),
+            None,
+        ),
+        Orientation::SideBySide => (
+            Rect::new(area.x, area.y, size, area.height),
+            Rect::new(area.x + size + 1, area.y, available - size, area.height),
+            if area.width > 0 { Some(Rect::new(area.x + size, area.y, 1, area.height)) } else { None },
+        ),
+    }
+}
+
+// whether a..a+a_size and b..b+b_size have something in common
+fn overlap(a: usize, a_size: usize, b: usize, b_size: usize) -> bool {
+    a < b + b_size && b < a + a_size
+}
+
+// The view next to `from` in the direction of an arrow key: the closest one
+// past that edge of it, that's level with it.
+pub fn neighbour(views: &[(usize, Rect)], from: usize, direction: Key) -> Option<usize> {
+    let &(_, area) = views.iter().find(|&&(view, _)| view == from)?;
+    let distance = |other: &Rect| match direction {
+        Key::Left if other.x + other.width <= area.x && overlap(area.y, area.height, other.y, other.height) => {
+            Some((area.x - other.x - other.width, other.y.abs_diff(area.y)))
+        },
+        Key::Right if other.x >= area.x + area.width && overlap(area.y, area.height, other.y, other.height) => {
+            Some((other.x - area.x - area.width, other.y.abs_diff(area.y)))
+        },
+        Key::Up if other.y + other.height <= area.y && overlap(area.x, area.width, other.x, other.width) => {
+            Some((area.y - other.y - other.height, other.x.abs_diff(area.x)))
+        },
+        Key::Down if other.y >= area.y + area.height && overlap(area.x, area.width, other.x, other.width) => {
+            Some((other.y - area.y - area.height, other.x.abs_diff(area.x)))
+        },
+        _ => None,
+    };
+    views.iter()
+        .filter_map(|&(view, ref other)| distance(other).map(|distance| (distance, view)))
+        .min()
+        .map(|(_, view)| view)
+}
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn screen() -> Rect {
+        Rect::new(0, 0, 81, 20)
+    }
+
+    #[test]
+    fn test_splitting() {
+        let mut layout = Layout::View(0);
+        assert!(layout.split(0, 1, Orientation::SideBySide, screen()));
+        assert!(layout.split(1, 2, Orientation::Stacked, screen()));
+        assert!(!layout.split(3, 4, Orientation::Stacked, screen()));
+        let (views, separators) = layout.arrange(screen());
+        assert_eq!(views, vec![(0, Rect::new(0, 0, 40, 20)), (1, Rect::new(41, 0, 40, 10)),
+                               (2, Rect::new(41, 10, 40, 10))]);
+        assert_eq!(separators, vec![Rect::new(40, 0, 1, 20)]);
+
+        // no room for two views in three rows
+        let mut layout = Layout::View(0);
+        assert!(!layout.split(0, 1, Orientation::Stacked, Rect::new(0, 0, 10, 3)));
+    }
+
+    #[test]
+    fn test_removing() {
+        let mut layout = Layout::View(0);
+        assert!(!layout.remove(0));
+        layout.split(0, 1, Orientation::Stacked, screen());
+        layout.split(0, 2, Orientation::SideBySide, screen());
+        assert!(layout.remove(0));
+        // the others move down
+        let (views, separators) = layout.arrange(screen());
+        assert_eq!(views, vec![(1, Rect::new(0, 0, 81, 10)), (0, Rect::new(0, 10, 81, 10))]);
+        assert_eq!(separators, vec![]);
+    }
+
+    #[test]
+    fn test_resizing() {
+        let mut layout = Layout::View(0);
+        assert!(!layout.resize(0, 1, screen()));
+        layout.split(0, 1, Orientation::Stacked, screen());
+        assert!(layout.resize(1, 3, screen()));
+        assert_eq!(layout.arrange(screen()).0, vec![(0, Rect::new(0, 0, 81, 7)), (1, Rect::new(0, 7, 81, 13))]);
+        // down to the smallest a view can be
+        assert!(layout.resize(0, -10, screen()));
+        assert_eq!(layout.arrange(screen()).0[0], (0, Rect::new(0, 0, 81, 2)));
+        // it keeps its share of a bigger screen
+        assert_eq!(layout.arrange(Rect::new(0, 0, 81, 40)).0[0], (0, Rect::new(0, 0, 81, 4)));
+    }
+
+    #[test]
+    fn test_neighbours() {
+        let mut layout = Layout::View(0);
+        layout.split(0, 1, Orientation::SideBySide, screen());
+        layout.split(1, 2, Orientation::Stacked, screen());
+        let (views, _) = layout.arrange(screen());
+        assert_eq!(neighbour(&views, 0, Key::Right), Some(1));
+        assert_eq!(neighbour(&views, 2, Key::Left), Some(0));
+        assert_eq!(neighbour(&views, 2, Key::Up), Some(1));
+        assert_eq!(neighbour(&views, 1, Key::Down), Some(2));
+        assert_eq!(neighbour(&views, 0, Key::Left), None);
+        assert_eq!(neighbour(&views, 0, Key::Up), None);
+    }
+}
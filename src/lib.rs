@@ -0,0 +1,56 @@
+//! A small terminal text editor, and the parts it's made of.
+//!
+//! The editing engine works without a screen: a [`buffer::Buffer`] holds the
+//! text and its undo history, [`commands`] applies keys to it and
+//! [`io`] reads and writes files. On top of that a [`display::Display`] draws
+//! a buffer through a [`backend::Backend`], the terminal or an in-memory
+//! [`grid::Grid`], and an [`editor::Editor`] puts it all together the way the
+//! editor2 binary uses it.
+//!
+//! ```
+//! use editor2::{commands, Buffer, Cursor, Key};
+//!
+//! let mut buffer = Buffer::from_string("fn main() {}\n");
+//! let (_, cursor) = commands::apply_command(Key::Char('p'), &mut buffer, &Cursor::new(0, 0));
+//! assert_eq!(buffer.get_line(0), "pfn main() {}");
+//! assert_eq!(cursor, Cursor::new(1, 0));
+//! buffer.undo();
+//! assert_eq!(buffer.get_line(0), "fn main() {}");
+//! ```
+
+#![cfg_attr(all(feature = "nightly", test), feature(test))]
+
+extern crate regex;
+extern crate rustbox;
+
+pub mod backend;
+pub mod buffer;
+pub mod clipboard;
+pub mod commands;
+mod config;
+pub mod cursor;
+pub mod display;
+pub mod editor;
+pub mod error;
+mod file;
+pub mod grid;
+mod gutter;
+pub mod highlight;
+mod history;
+mod input;
+pub mod io;
+pub mod language;
+mod layout;
+mod picker;
+mod replace;
+mod rope;
+mod search;
+mod swap;
+pub mod theme;
+mod wrap;
+#[cfg(all(feature = "nightly", test))]
+mod bench;
+
+pub use buffer::{Buffer, BufferChanges, LineEnding};
+pub use cursor::{Cursor, Region};
+pub use backend::Key;
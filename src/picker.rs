@@ -0,0 +1,123 @@
+// Picking one out of a list, like the open buffers.
+//
+// The list takes the screen while the picker is up. Typing narrows it down to
+// the items with what was typed in them, up and down move between what's
+// left, Enter picks one and Esc gives up.
+
+use backend::Key;
+
+pub struct Picker {
+    title: String,
+    items: Vec<String>,
+    query: String,
+    // position in what's shown, not in items
+    selected: usize,
+}
+
+pub enum Choice {
+    Picking,
+    // the item's position in the list we started with
+    Picked(usize),
+    Cancelled,
+}
+
+impl Picker {
+    // starts out on item `selected`
+    pub fn new(title: &str, items: Vec<String>, selected: usize) -> Picker {
+        Picker {
+            title: title.to_string(),
+            items,
+            query: String::new(),
+            selected,
+        }
+    }
+
+    pub fn prompt(&self) -> String {
+        format!("{}: {}", self.title, self.query)
+    }
+
+    // the items that match the query, as positions in the list
+    pub fn shown(&self) -> Vec<usize> {
+        (0..self.items.len()).filter(|&i| self.items[i].contains(&self.query[..])).collect()
+    }
+
+    // what's shown, and which of it is selected
+    pub fn lines(&self) -> (Vec<&str>, usize) {
+        (self.shown().into_iter().map(|i| &self.items[i][..]).collect(), self.selected)
+    }
+
+    pub fn handle_key(&mut self, key: Key) -> Choice {
+        let shown = self.shown();
+        match key {
+            Key::Enter                => {
+                return match shown.get(self.selected) {
+                    Some(&item) => Choice::Picked(item),
+                    None => Choice::Picking,
+                };
+            },
+            Key::Esc | Key::Ctrl('g') => return Choice::Cancelled,
+            Key::Up                   => self.selected = self.selected.saturating_sub(1),
+            Key::Down                 => self.selected = (self.selected + 1).min(shown.len().saturating_sub(1)),
+            Key::Backspace            => {
+                self.query.pop();
+                self.selected = 0;
+            },
+            Key::Char(character)      => {
+                self.query.push(character);
+                self.selected = 0;
+            },
+            _                         => { },
+        }
+        Choice::Picking
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn picker() -> Picker {
+        let items = vec!["src/main.rs".to_string(), "README.md".to_string(), "src/lib.rs".to_string()];
+        Picker::new("Buffer", items, 1)
+    }
+
+    fn picked(choice: Choice) -> Option<usize> {
+        match choice {
+            Choice::Picked(item) => Some(item),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_moving_and_picking() {
+        let mut picker = picker();
+        assert_eq!(picker.lines(), (vec!["src/main.rs", "README.md", "src/lib.rs"], 1));
+        picker.handle_key(Key::Down);
+        picker.handle_key(Key::Down);
+        assert_eq!(picker.lines().1, 2);
+        assert_eq!(picked(picker.handle_key(Key::Enter)), Some(2));
+        picker.handle_key(Key::Up);
+        assert_eq!(picked(picker.handle_key(Key::Enter)), Some(1));
+        assert!(matches!(picker.handle_key(Key::Esc), Choice::Cancelled));
+    }
+
+    #[test]
+    fn test_typing_narrows_the_list() {
+        let mut picker = picker();
+        for character in "src/".chars() {
+            picker.handle_key(Key::Char(character));
+        }
+        assert_eq!(picker.prompt(), "Buffer: src/");
+        assert_eq!(picker.lines(), (vec!["src/main.rs", "src/lib.rs"], 0));
+        picker.handle_key(Key::Down);
+        assert_eq!(picked(picker.handle_key(Key::Enter)), Some(2));
+
+        picker.handle_key(Key::Char('x'));
+        assert_eq!(picker.lines(), (vec![], 0));
+        assert!(matches!(picker.handle_key(Key::Enter), Choice::Picking));
+        picker.handle_key(Key::Backspace);
+        assert_eq!(picker.lines().0.len(), 2);
+    }
+}
@@ -0,0 +1,366 @@
+// Regex find and replace.
+//
+// A Replace asks for a pattern, then for what to put instead, and then goes
+// through the matches one by one asking what to do with each: y replaces it,
+// n skips it, a replaces it and every one after it, q stops. The replacement
+// can refer to capture groups as $1 or ${name}.
+//
+// Matches are all found up front on the text as it was when we started, and
+// the replacements shift the ones after them, which we keep track of. Every
+// replacement made in one go is committed as a single change, so it's undone
+// all at once.
+
+use regex::{Regex, RegexBuilder};
+
+use backend::Key;
+use history::ChangeKind;
+use {Buffer, Cursor, Region};
+
+enum Stage {
+    Pattern,
+    Replacement(Regex),
+    Confirming,
+}
+
+// a match on the original text, with what it'll be replaced by
+struct Match {
+    start: usize,
+    end: usize,
+    replacement: String,
+}
+
+pub struct Replace {
+    stage: Stage,
+    // what's being typed in the prompt
+    input: String,
+    error: Option<String>,
+    pattern: String,
+    // only matches inside this are replaced
+    region: Option<Region>,
+    // where the cursor was when we started
+    origin: Cursor,
+    matches: Vec<Match>,
+    // the match we're asking about
+    next: usize,
+    // chars added (or removed, when negative) by the replacements so far
+    shift: isize,
+    replaced: usize,
+    // where the last replacement ended
+    last_end: Option<usize>,
+}
+
+pub enum Step {
+    // waiting for more keys, the cursor should be here
+    Asking(Cursor),
+    // we're done, the cursor should be here
+    Done(Cursor),
+    // given up on before getting to the matches, back to where we started
+    Cancelled(Cursor),
+}
+
+impl Replace {
+    pub fn new(origin: Cursor, region: Option<Region>) -> Replace {
+        Replace {
+            stage: Stage::Pattern,
+            input: String::new(),
+            error: None,
+            pattern: String::new(),
+            region,
+            origin,
+            matches: Vec::new(),
+            next: 0,
+            shift: 0,
+            replaced: 0,
+            last_end: None,
+        }
+    }
+
+    pub fn prompt(&self) -> String {
+        let scope = if self.region.is_some() { " in selection" } else { "" };
+        match self.stage {
+            Stage::Pattern => match self.error {
+                Some(ref error) => format!("{}, replace regex{}: {}", error, scope, self.input),
+                None => format!("Replace regex{}: {}", scope, self.input),
+            },
+            Stage::Replacement(_) => format!("Replace {} with: {}", self.pattern, self.input),
+            Stage::Confirming => {
+                format!("Replace this match? (y/n/a/q) [{} of {}]", self.next + 1, self.matches.len())
+            },
+        }
+    }
+
+    // the match we're asking about, to highlight it
+    pub fn current_match(&self, buffer: &Buffer) -> Option<Region> {
+        match self.stage {
+            Stage::Confirming => {
+                let (start, end) = self.position(self.next)?;
+                Some(Region::new(buffer.char_to_cursor(start), buffer.char_to_cursor(end)))
+            },
+            _ => None,
+        }
+    }
+
+    // how many matches were replaced
+    pub fn replaced(&self) -> usize {
+        self.replaced
+    }
+
+    pub fn handle_key(&mut self, key: Key, buffer: &mut Buffer, cursor: &Cursor) -> Step {
+        match self.stage {
+            Stage::Confirming => self.confirm(key, buffer),
+            _ => match key {
+                Key::Esc | Key::Ctrl('g') => Step::Cancelled(self.origin),
+                Key::Enter => self.accept_input(buffer),
+                Key::Backspace => {
+                    self.input.pop();
+                    Step::Asking(*cursor)
+                },
+                Key::Char(character) => {
+                    self.input.push(character);
+                    Step::Asking(*cursor)
+                },
+                _ => Step::Asking(*cursor),
+            },
+        }
+    }
+
+    fn accept_input(&mut self, buffer: &Buffer) -> Step {
+        let input = self.input.split_off(0);
+        match self.stage {
+            Stage::Pattern => {
+                // ^ and $ match at line boundaries, as you'd expect in an editor
+                match RegexBuilder::new(&input).multi_line(true).build() {
+                    Ok(regex) => {
+                        self.error = None;
+                        self.pattern = input;
+                        self.stage = Stage::Replacement(regex);
+                    },
+                    Err(_) => {
+                        self.error = Some("Invalid regex".to_string());
+                        self.input = input;
+                    },
+                }
+                Step::Asking(self.origin)
+            },
+            Stage::Replacement(ref regex) => {
+                self.matches = find_matches(buffer, regex, &input, self.region);
+                if self.matches.is_empty() {
+                    return Step::Done(self.origin);
+                }
+                self.stage = Stage::Confirming;
+                Step::Asking(buffer.char_to_cursor(self.matches[0].start))
+            },
+            Stage::Confirming => unreachable!(),
+        }
+    }
+
+    fn confirm(&mut self, key: Key, buffer: &mut Buffer) -> Step {
+        match key {
+            Key::Char('y') => self.replace_next(buffer),
+            Key::Char('n') => self.next += 1,
+            Key::Char('a') => {
+                while self.next < self.matches.len() {
+                    self.replace_next(buffer);
+                }
+            },
+            Key::Char('q') | Key::Esc | Key::Ctrl('g') => self.next = self.matches.len(),
+            _ => {},
+        }
+
+        match self.position(self.next) {
+            Some((start, _)) => Step::Asking(buffer.char_to_cursor(start)),
+            None => Step::Done(self.finish(buffer)),
+        }
+    }
+
+    fn replace_next(&mut self, buffer: &mut Buffer) {
+        let (start, end) = self.position(self.next).unwrap();
+        let replacement = &self.matches[self.next].replacement;
+        buffer.replace_text(start, end, replacement);
+        let length = replacement.chars().count();
+        self.shift += length as isize - (end - start) as isize;
+        self.last_end = Some(start + length);
+        self.replaced += 1;
+        self.next += 1;
+    }
+
+    // Commits every replacement as one change, the cursor ends up after the
+    // last one.
+    fn finish(&mut self, buffer: &mut Buffer) -> Cursor {
+        let end = match self.last_end {
+            Some(end) => end,
+            None => return self.origin,
+        };
+        let cursor = buffer.char_to_cursor(end);
+        buffer.history.commit(ChangeKind::Other, self.origin, cursor);
+        cursor
+    }
+
+    // Where match `index` is now. Replacements are done in order, so this is
+    // only right for the matches we haven't got to yet.
+    fn position(&self, index: usize) -> Option<(usize, usize)> {
+        let found = self.matches.get(index)?;
+        Some(((found.start as isize + self.shift) as usize, (found.end as isize + self.shift) as usize))
+    }
+}
+
+// Every match of `regex` in the buffer, or in `region` if given, with the
+// replacement expanded. The last newline is left out so the buffer always
+// ends in one.
+fn find_matches(buffer: &Buffer, regex: &Regex, replacement: &str, region: Option<Region>) -> Vec<Match> {
+    let length = buffer.text.len_chars();
+    if length == 0 {
+        return Vec::new();
+    }
+    let (base, end) = match region {
+        Some(region) => (buffer.cursor_to_char(&region.start), buffer.cursor_to_char(&region.end)),
+        None => (0, length),
+    };
+    let end = end.min(length - 1);
+    if base > end || (region.is_some() && base == end) {
+        return Vec::new();
+    }
+    let text = buffer.text.slice(base, end);
+
+    let mut matches = Vec::new();
+    let (mut last_byte, mut last_char) = (0, 0);
+    for captures in regex.captures_iter(&text) {
+        let whole = captures.get(0).unwrap();
+        let start = last_char + text[last_byte..whole.start()].chars().count();
+        let end = start + whole.as_str().chars().count();
+        let mut expanded = String::new();
+        captures.expand(replacement, &mut expanded);
+        matches.push(Match {start: base + start, end: base + end, replacement: expanded});
+        last_byte = whole.end();
+        last_char = end;
+    }
+    matches
+}
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use backend::Key;
+    use {Buffer, Cursor, Region};
+
+    fn type_in(replace: &mut Replace, buffer: &mut Buffer, text: &str) -> Step {
+        let cursor = Cursor::new(0, 0);
+        for character in text.chars() {
+            replace.handle_key(Key::Char(character), buffer, &cursor);
+        }
+        replace.handle_key(Key::Enter, buffer, &cursor)
+    }
+
+    fn start(buffer: &mut Buffer, pattern: &str, replacement: &str, region: Option<Region>) -> Replace {
+        let mut replace = Replace::new(Cursor::new(0, 0), region);
+        type_in(&mut replace, buffer, pattern);
+        type_in(&mut replace, buffer, replacement);
+        replace
+    }
+
+    fn text(buffer: &Buffer) -> String {
+        buffer.text.to_string()
+    }
+
+    #[test]
+    fn test_replace_all_with_capture_groups() {
+        let mut buffer = Buffer::from_string("let a = f(1);\nlet bb = f(22);\n");
+        let mut replace = start(&mut buffer, r"let (\w+) = f\((?P<arg>\d+)\)", "let $1 = g(${arg}, $1)", None);
+        assert_eq!(replace.current_match(&buffer), Some(Region::new(Cursor::new(0, 0), Cursor::new(12, 0))));
+        match replace.handle_key(Key::Char('a'), &mut buffer, &Cursor::new(0, 0)) {
+            Step::Done(cursor) => assert_eq!(cursor, Cursor::new(18, 1)),
+            _ => panic!("replacing all should be done"),
+        }
+        assert_eq!(text(&buffer), "let a = g(1, a);\nlet bb = g(22, bb);\n");
+        assert_eq!(replace.replaced(), 2);
+
+        // a single undo brings everything back
+        assert_eq!(buffer.undo(), Some(Cursor::new(0, 0)));
+        assert_eq!(text(&buffer), "let a = f(1);\nlet bb = f(22);\n");
+        assert_eq!(buffer.undo(), None);
+    }
+
+    #[test]
+    fn test_confirm_each_match() {
+        let mut buffer = Buffer::from_string("foo foo\nfoo\nfoo");
+        let mut replace = start(&mut buffer, "foo", "barbaz", None);
+        let cursor = Cursor::new(0, 0);
+
+        match replace.handle_key(Key::Char('y'), &mut buffer, &cursor) {
+            Step::Asking(cursor) => assert_eq!(cursor, Cursor::new(7, 0)),
+            _ => panic!("there are more matches"),
+        }
+        assert_eq!(replace.prompt(), "Replace this match? (y/n/a/q) [2 of 4]");
+        match replace.handle_key(Key::Char('n'), &mut buffer, &cursor) {
+            Step::Asking(cursor) => assert_eq!(cursor, Cursor::new(0, 1)),
+            _ => panic!("there are more matches"),
+        }
+        replace.handle_key(Key::Char('y'), &mut buffer, &cursor);
+        match replace.handle_key(Key::Char('q'), &mut buffer, &cursor) {
+            Step::Done(cursor) => assert_eq!(cursor, Cursor::new(6, 1)),
+            _ => panic!("q should stop"),
+        }
+        assert_eq!(text(&buffer), "barbaz foo\nbarbaz\nfoo\n");
+
+        buffer.undo();
+        assert_eq!(text(&buffer), "foo foo\nfoo\nfoo\n");
+    }
+
+    #[test]
+    fn test_replace_in_selection() {
+        let mut buffer = Buffer::from_string("x x\nx x\nx x");
+        let region = Region::new(Cursor::new(2, 0), Cursor::new(1, 2));
+        let mut replace = start(&mut buffer, "x", "y", Some(region));
+        replace.handle_key(Key::Char('a'), &mut buffer, &Cursor::new(0, 0));
+        assert_eq!(text(&buffer), "x y\ny y\ny x\n");
+    }
+
+    #[test]
+    fn test_line_anchors_keep_the_last_newline() {
+        let mut buffer = Buffer::from_string("one\ntwo");
+        let mut replace = start(&mut buffer, "^", "// ", None);
+        replace.handle_key(Key::Char('a'), &mut buffer, &Cursor::new(0, 0));
+        assert_eq!(text(&buffer), "// one\n// two\n");
+
+        let mut replace = start(&mut buffer, r"\n", " ", None);
+        replace.handle_key(Key::Char('a'), &mut buffer, &Cursor::new(0, 0));
+        assert_eq!(text(&buffer), "// one // two\n");
+    }
+
+    #[test]
+    fn test_invalid_regex_and_no_matches() {
+        let mut buffer = Buffer::from_string("text");
+        let mut replace = Replace::new(Cursor::new(0, 0), None);
+        type_in(&mut replace, &mut buffer, "(");
+        assert_eq!(replace.prompt(), "Invalid regex, replace regex: (");
+
+        replace.handle_key(Key::Backspace, &mut buffer, &Cursor::new(0, 0));
+        type_in(&mut replace, &mut buffer, "nope");
+        match type_in(&mut replace, &mut buffer, "x") {
+            Step::Done(cursor) => assert_eq!(cursor, Cursor::new(0, 0)),
+            _ => panic!("nothing to replace"),
+        }
+        assert_eq!(buffer.undo(), None);
+    }
+
+    #[test]
+    fn test_cancelling_at_the_prompts() {
+        let mut buffer = Buffer::from_string("text");
+        let origin = Cursor::new(2, 0);
+        let mut replace = Replace::new(origin, None);
+        match replace.handle_key(Key::Esc, &mut buffer, &origin) {
+            Step::Cancelled(cursor) => assert_eq!(cursor, origin),
+            _ => panic!("Esc cancels"),
+        }
+
+        let mut replace = Replace::new(origin, None);
+        type_in(&mut replace, &mut buffer, "t");
+        match replace.handle_key(Key::Ctrl('g'), &mut buffer, &origin) {
+            Step::Cancelled(cursor) => assert_eq!(cursor, origin),
+            _ => panic!("Ctrl-G cancels"),
+        }
+        assert_eq!(buffer.get_line(0), "text");
+    }
+}
@@ -0,0 +1,546 @@
+// A rope: a height balanced binary tree whose leaves are UTF-8 chunks.
+//
+// Every node caches the number of bytes, chars and newlines below it, so
+// finding a char offset or the start of a line is a walk from the root to a
+// single leaf. Inserts and removals only touch the nodes along that walk and
+// rebalance them on the way back up.
+
+use std::fmt;
+use std::mem;
+
+// biggest leaf we'll keep around, in bytes
+const MAX_LEAF: usize = 1024;
+// new leaves are cut smaller so a few inserts don't make them split right away
+const BUILD_LEAF: usize = MAX_LEAF * 3 / 4;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+struct Info {
+    bytes: usize,
+    chars: usize,
+    newlines: usize,
+}
+
+impl Info {
+    fn of(text: &str) -> Info {
+        Info {
+            bytes: text.len(),
+            chars: text.chars().count(),
+            newlines: text.bytes().filter(|&b| b == b'\n').count(),
+        }
+    }
+
+    fn is_ascii(&self) -> bool {
+        self.bytes == self.chars
+    }
+
+    fn add(self, other: Info) -> Info {
+        Info {
+            bytes: self.bytes + other.bytes,
+            chars: self.chars + other.chars,
+            newlines: self.newlines + other.newlines,
+        }
+    }
+
+    fn sub(self, other: Info) -> Info {
+        Info {
+            bytes: self.bytes - other.bytes,
+            chars: self.chars - other.chars,
+            newlines: self.newlines - other.newlines,
+        }
+    }
+}
+
+enum Node {
+    Leaf {
+        text: String,
+        info: Info,
+    },
+    Branch {
+        left: Box<Node>,
+        right: Box<Node>,
+        info: Info,
+        height: usize,
+    },
+}
+
+// byte offset of the `char_idx`th char in `text`
+fn char_to_byte(text: &str, char_idx: usize) -> usize {
+    text.char_indices().nth(char_idx).map_or(text.len(), |(i, _)| i)
+}
+
+// byte range of the chars `start..end` of a leaf, skipping the scan when the
+// leaf is plain ascii (most source code is)
+fn leaf_byte_range(text: &str, info: Info, start: usize, end: usize) -> (usize, usize) {
+    if info.is_ascii() {
+        return (start, end);
+    }
+    let start_byte = char_to_byte(text, start);
+    (start_byte, start_byte + char_to_byte(&text[start_byte..], end - start))
+}
+
+impl Node {
+    fn leaf(text: String) -> Node {
+        let info = Info::of(&text);
+        Node::Leaf { text, info }
+    }
+
+    fn empty() -> Node {
+        Node::leaf(String::new())
+    }
+
+    // plain constructor, doesn't look at balance
+    fn branch(left: Node, right: Node) -> Node {
+        let info = left.info().add(right.info());
+        let height = 1 + left.height().max(right.height());
+        Node::Branch { left: Box::new(left), right: Box::new(right), info, height }
+    }
+
+    fn build(text: &str) -> Node {
+        let mut leaves = Vec::new();
+        let mut rest = text;
+        while rest.len() > MAX_LEAF {
+            let mut split = BUILD_LEAF;
+            while !rest.is_char_boundary(split) { split -= 1; }
+            let (chunk, tail) = rest.split_at(split);
+            leaves.push(Node::leaf(chunk.to_string()));
+            rest = tail;
+        }
+        leaves.push(Node::leaf(rest.to_string()));
+
+        Node::from_leaves(leaves)
+    }
+
+    // split in halves so sibling heights never differ by more than one
+    fn from_leaves(mut leaves: Vec<Node>) -> Node {
+        if leaves.len() == 1 {
+            return leaves.pop().unwrap();
+        }
+        let right = leaves.split_off(leaves.len() / 2);
+        Node::branch(Node::from_leaves(leaves), Node::from_leaves(right))
+    }
+
+    fn info(&self) -> Info {
+        match *self {
+            Node::Leaf { info, .. } | Node::Branch { info, .. } => info,
+        }
+    }
+
+    fn height(&self) -> usize {
+        match *self {
+            Node::Leaf { .. } => 0,
+            Node::Branch { height, .. } => height,
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.info().bytes == 0
+    }
+
+    fn into_children(self) -> (Node, Node) {
+        match self {
+            Node::Branch { left, right, .. } => (*left, *right),
+            Node::Leaf { .. } => unreachable!(),
+        }
+    }
+
+    // Concatenate two balanced trees into a balanced tree. Heights may differ
+    // by any amount: we walk down the spine of the taller one until the
+    // heights match and rotate on the way back up.
+    fn join(left: Node, right: Node) -> Node {
+        if left.is_empty() { return right; }
+        if right.is_empty() { return left; }
+
+        let (left_height, right_height) = (left.height(), right.height());
+        if left_height > right_height + 1 {
+            let (left_left, left_right) = left.into_children();
+            Node::balance(left_left, Node::join(left_right, right))
+        } else if right_height > left_height + 1 {
+            let (right_left, right_right) = right.into_children();
+            Node::balance(Node::join(left, right_left), right_right)
+        } else {
+            match (left, right) {
+                (Node::Leaf { text: mut a, .. }, Node::Leaf { text: b, .. }) => {
+                    if a.len() + b.len() <= MAX_LEAF {
+                        a.push_str(&b);
+                        Node::leaf(a)
+                    } else {
+                        Node::branch(Node::leaf(a), Node::leaf(b))
+                    }
+                }
+                (left, right) => Node::branch(left, right),
+            }
+        }
+    }
+
+    // single or double rotation for trees that are off by two
+    fn balance(left: Node, right: Node) -> Node {
+        let (left_height, right_height) = (left.height(), right.height());
+        if left_height > right_height + 1 {
+            let (left_left, left_right) = left.into_children();
+            if left_left.height() >= left_right.height() {
+                Node::branch(left_left, Node::branch(left_right, right))
+            } else {
+                let (middle_left, middle_right) = left_right.into_children();
+                Node::branch(Node::branch(left_left, middle_left),
+                             Node::branch(middle_right, right))
+            }
+        } else if right_height > left_height + 1 {
+            let (right_left, right_right) = right.into_children();
+            if right_right.height() >= right_left.height() {
+                Node::branch(Node::branch(left, right_left), right_right)
+            } else {
+                let (middle_left, middle_right) = right_left.into_children();
+                Node::branch(Node::branch(left, middle_left),
+                             Node::branch(middle_right, right_right))
+            }
+        } else {
+            Node::branch(left, right)
+        }
+    }
+
+    // refresh cached data after a child changed, rebuilding when needed
+    fn fix(&mut self) {
+        let needs_join = match *self {
+            Node::Leaf { .. } => false,
+            Node::Branch { ref left, ref right, ref mut info, ref mut height } => {
+                *info = left.info().add(right.info());
+                *height = 1 + left.height().max(right.height());
+                left.is_empty() || right.is_empty() ||
+                    left.height() > right.height() + 1 ||
+                    right.height() > left.height() + 1 ||
+                    (left.height() == 0 && right.height() == 0 &&
+                     info.bytes <= MAX_LEAF)
+            }
+        };
+        if needs_join {
+            let (left, right) = mem::replace(self, Node::empty()).into_children();
+            *self = Node::join(left, right);
+        }
+    }
+
+    fn insert(&mut self, char_idx: usize, text: &str) {
+        match *self {
+            Node::Leaf { text: ref mut leaf_text, ref mut info } => {
+                let (byte_idx, _) = leaf_byte_range(leaf_text, *info, char_idx, char_idx);
+                leaf_text.insert_str(byte_idx, text);
+                *info = info.add(Info::of(text));
+                if info.bytes <= MAX_LEAF { return; }
+            }
+            Node::Branch { ref mut left, ref mut right, .. } => {
+                let left_chars = left.info().chars;
+                if char_idx <= left_chars {
+                    left.insert(char_idx, text);
+                } else {
+                    right.insert(char_idx - left_chars, text);
+                }
+            }
+        }
+
+        if let Node::Leaf { ref text, .. } = *self {
+            // leaf grew too big, turn it into a subtree
+            let subtree = Node::build(text);
+            *self = subtree;
+        } else {
+            self.fix();
+        }
+    }
+
+    fn remove(&mut self, start: usize, end: usize) {
+        match *self {
+            Node::Leaf { ref mut text, ref mut info } => {
+                let (start_byte, end_byte) = leaf_byte_range(text, *info, start, end);
+                *info = info.sub(Info::of(&text[start_byte..end_byte]));
+                text.drain(start_byte..end_byte);
+                return;
+            }
+            Node::Branch { ref mut left, ref mut right, .. } => {
+                let left_chars = left.info().chars;
+                if start < left_chars {
+                    left.remove(start, end.min(left_chars));
+                }
+                if end > left_chars {
+                    right.remove(start.max(left_chars) - left_chars, end - left_chars);
+                }
+            }
+        }
+        self.fix();
+    }
+
+    // char offset right after the `n`th newline, n >= 1
+    fn after_newline(&self, n: usize) -> usize {
+        match *self {
+            Node::Leaf { ref text, info } => {
+                let newline = text.bytes().enumerate()
+                    .filter(|&(_, byte)| byte == b'\n')
+                    .nth(n - 1)
+                    .map_or(text.len(), |(i, _)| i + 1);
+                if info.is_ascii() {
+                    newline
+                } else {
+                    text[..newline].chars().count()
+                }
+            }
+            Node::Branch { ref left, ref right, .. } => {
+                let left_info = left.info();
+                if n <= left_info.newlines {
+                    left.after_newline(n)
+                } else {
+                    left_info.chars + right.after_newline(n - left_info.newlines)
+                }
+            }
+        }
+    }
+
+    fn newlines_before(&self, char_idx: usize) -> usize {
+        match *self {
+            Node::Leaf { ref text, info } => {
+                let (byte_idx, _) = leaf_byte_range(text, info, char_idx, char_idx);
+                text[..byte_idx].bytes().filter(|&b| b == b'\n').count()
+            }
+            Node::Branch { ref left, ref right, .. } => {
+                let left_info = left.info();
+                if char_idx <= left_info.chars {
+                    left.newlines_before(char_idx)
+                } else {
+                    left_info.newlines + right.newlines_before(char_idx - left_info.chars)
+                }
+            }
+        }
+    }
+
+    fn push_slice(&self, start: usize, end: usize, out: &mut String) {
+        match *self {
+            Node::Leaf { ref text, info } => {
+                let (start_byte, end_byte) = leaf_byte_range(text, info, start, end);
+                out.push_str(&text[start_byte..end_byte]);
+            }
+            Node::Branch { ref left, ref right, .. } => {
+                let left_chars = left.info().chars;
+                if start < left_chars {
+                    left.push_slice(start, end.min(left_chars), out);
+                }
+                if end > left_chars {
+                    right.push_slice(start.max(left_chars) - left_chars, end - left_chars, out);
+                }
+            }
+        }
+    }
+}
+
+pub struct Rope {
+    root: Node,
+}
+
+impl Rope {
+    pub fn new() -> Rope {
+        Rope { root: Node::empty() }
+    }
+
+    pub fn from_string(text: &str) -> Rope {
+        Rope { root: Node::build(text) }
+    }
+
+    pub fn len_chars(&self) -> usize {
+        self.root.info().chars
+    }
+
+    pub fn count_newlines(&self) -> usize {
+        self.root.info().newlines
+    }
+
+    // char offset where line `line_number` starts, counting lines as the text
+    // between newlines. Past the last newline this is the end of the text.
+    pub fn line_to_char(&self, line_number: usize) -> usize {
+        if line_number == 0 {
+            0
+        } else if line_number > self.count_newlines() {
+            self.len_chars()
+        } else {
+            self.root.after_newline(line_number)
+        }
+    }
+
+    // the line `char_idx` is on, that is, how many newlines come before it
+    pub fn char_to_line(&self, char_idx: usize) -> usize {
+        assert!(char_idx <= self.len_chars(), "char_to_line out of bounds");
+        self.root.newlines_before(char_idx)
+    }
+
+    pub fn insert(&mut self, char_idx: usize, text: &str) {
+        assert!(char_idx <= self.len_chars(), "insert out of bounds");
+        if text.is_empty() { return; }
+        self.root.insert(char_idx, text);
+    }
+
+    #[cfg(test)]
+    pub fn insert_char(&mut self, char_idx: usize, character: char) {
+        let mut encoded = [0; 4];
+        self.insert(char_idx, character.encode_utf8(&mut encoded));
+    }
+
+    pub fn remove(&mut self, start: usize, end: usize) {
+        assert!(start <= end && end <= self.len_chars(), "remove out of bounds");
+        if start == end { return; }
+        self.root.remove(start, end);
+    }
+
+    pub fn slice(&self, start: usize, end: usize) -> String {
+        assert!(start <= end && end <= self.len_chars(), "slice out of bounds");
+        let mut result = String::with_capacity(end - start);
+        if start < end {
+            self.root.push_slice(start, end, &mut result);
+        }
+        result
+    }
+
+    pub fn chunks(&self) -> Chunks<'_> {
+        Chunks { stack: vec![&self.root] }
+    }
+}
+
+impl Default for Rope {
+    fn default() -> Rope {
+        Rope::new()
+    }
+}
+
+impl fmt::Display for Rope {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        for chunk in self.chunks() {
+            formatter.write_str(chunk)?;
+        }
+        Ok(())
+    }
+}
+
+// in-order walk over the leaves
+pub struct Chunks<'a> {
+    stack: Vec<&'a Node>,
+}
+
+impl<'a> Iterator for Chunks<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<&'a str> {
+        while let Some(node) = self.stack.pop() {
+            match *node {
+                Node::Leaf { ref text, .. } => {
+                    if !text.is_empty() { return Some(text); }
+                }
+                Node::Branch { ref left, ref right, .. } => {
+                    self.stack.push(right);
+                    self.stack.push(left);
+                }
+            }
+        }
+        None
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    // checks cached data and balance on every node, returns the height
+    fn check_node(node: &Node) -> usize {
+        match *node {
+            Node::Leaf { ref text, info } => {
+                assert_eq!(info, Info::of(text));
+                0
+            }
+            Node::Branch { ref left, ref right, info, height } => {
+                let left_height = check_node(left);
+                let right_height = check_node(right);
+                assert_eq!(info, left.info().add(right.info()));
+                assert_eq!(height, 1 + left_height.max(right_height));
+                assert!(left_height <= right_height + 1 && right_height <= left_height + 1);
+                height
+            }
+        }
+    }
+
+    #[test]
+    fn test_insert_and_remove_small_text() {
+        let mut rope = Rope::from_string("Hello world");
+        rope.insert(5, ",");
+        assert_eq!(rope.to_string(), "Hello, world");
+        rope.insert_char(12, '!');
+        assert_eq!(rope.to_string(), "Hello, world!");
+        rope.remove(0, 7);
+        assert_eq!(rope.to_string(), "world!");
+        assert_eq!(rope.len_chars(), 6);
+    }
+
+    #[test]
+    fn test_multibyte_characters() {
+        let mut rope = Rope::from_string("añb\n€c\n");
+        assert_eq!(rope.len_chars(), 7);
+        rope.insert_char(2, 'ü');
+        assert_eq!(rope.slice(0, 4), "añüb");
+        rope.remove(5, 6);
+        assert_eq!(rope.to_string(), "añüb\nc\n");
+    }
+
+    #[test]
+    fn test_line_to_char() {
+        let rope = Rope::from_string("one\ntwo\n\nfour\n");
+        assert_eq!(rope.count_newlines(), 4);
+        assert_eq!(rope.line_to_char(0), 0);
+        assert_eq!(rope.line_to_char(1), 4);
+        assert_eq!(rope.line_to_char(2), 8);
+        assert_eq!(rope.line_to_char(3), 9);
+        assert_eq!(rope.line_to_char(4), 14);
+        assert_eq!(rope.line_to_char(10), 14);
+
+        assert_eq!(rope.char_to_line(0), 0);
+        assert_eq!(rope.char_to_line(3), 0);
+        assert_eq!(rope.char_to_line(4), 1);
+        assert_eq!(rope.char_to_line(8), 2);
+        assert_eq!(rope.char_to_line(14), 4);
+    }
+
+    #[test]
+    fn test_large_text_stays_balanced() {
+        let line = "0123456789 abcdefghij ñ€\n";
+        let text: String = (0..1000).map(|_| line).collect();
+        let mut expected = text.clone();
+        let mut rope = Rope::from_string(&text);
+        check_node(&rope.root);
+
+        // poor man's random positions, keeps the test deterministic
+        let mut seed: usize = 12345;
+        for step in 0..1000 {
+            seed = seed.wrapping_mul(1103515245).wrapping_add(12345) % (1 << 31);
+            let chars = expected.chars().count();
+            let position = seed % (chars + 1);
+            let byte = char_to_byte(&expected, position);
+            if step % 3 == 0 && position < chars {
+                let end = (position + seed % 50).min(chars);
+                let end_byte = char_to_byte(&expected, end);
+                expected.drain(byte..end_byte);
+                rope.remove(position, end);
+            } else {
+                expected.insert_str(byte, "xy\nz");
+                rope.insert(position, "xy\nz");
+            }
+        }
+
+        check_node(&rope.root);
+        assert_eq!(rope.to_string(), expected);
+        assert_eq!(rope.count_newlines(), expected.matches('\n').count());
+        assert!(rope.root.height() < 20);
+    }
+
+    #[test]
+    fn test_remove_everything() {
+        let text: String = (0..10000).map(|_| "abc\n").collect();
+        let mut rope = Rope::from_string(&text);
+        rope.remove(0, rope.len_chars());
+        assert_eq!(rope.len_chars(), 0);
+        assert_eq!(rope.chunks().count(), 0);
+        rope.insert(0, "again");
+        assert_eq!(rope.to_string(), "again");
+    }
+}
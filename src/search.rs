@@ -0,0 +1,265 @@
+// Incremental search.
+//
+// While a Search is active every key edits the query or moves between
+// matches, and the cursor jumps to the closest match as the query changes.
+// Matches are looked for line by line and the search wraps around the ends
+// of the buffer.
+
+use backend::Key;
+use {Buffer, Cursor};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Direction {
+    Forward,
+    Backward,
+}
+
+pub struct Search {
+    pub query: String,
+    pub direction: Direction,
+    // where the cursor and the view were when the search started, to go back
+    // there if it's cancelled
+    pub origin: Cursor,
+    pub origin_offset: usize,
+    // whether the cursor is on a match of the current query
+    pub found: bool,
+}
+
+impl Search {
+    pub fn new(origin: Cursor, origin_offset: usize, direction: Direction) -> Search {
+        Search {
+            query: String::new(),
+            direction,
+            origin,
+            origin_offset,
+            found: true,
+        }
+    }
+
+    pub fn prompt(&self) -> String {
+        let direction = match self.direction {
+            Direction::Forward  => "Search",
+            Direction::Backward => "Search backward",
+        };
+        if self.found {
+            format!("{}: {}", direction, self.query)
+        } else {
+            format!("Failing {}: {}", direction.to_lowercase(), self.query)
+        }
+    }
+}
+
+pub enum Outcome {
+    // still searching, the cursor should be here
+    Searching(Cursor),
+    // stay where we are
+    Accepted,
+    // go back to the origin
+    Cancelled,
+}
+
+impl Search {
+    pub fn handle_key(&mut self, key: Key, buffer: &Buffer, cursor: &Cursor) -> Outcome {
+        match key {
+            Key::Enter              => Outcome::Accepted,
+            Key::Esc | Key::Ctrl('g') => Outcome::Cancelled,
+            Key::Ctrl('f')          => {
+                self.direction = Direction::Forward;
+                self.jump(buffer, cursor, true)
+            },
+            Key::Ctrl('r')          => {
+                self.direction = Direction::Backward;
+                self.jump(buffer, cursor, true)
+            },
+            Key::Backspace          => {
+                // a shorter query may match closer to where we started
+                self.query.pop();
+                let origin = self.origin;
+                self.jump(buffer, &origin, false)
+            },
+            Key::Char(character)    => {
+                self.query.push(character);
+                self.jump(buffer, cursor, false)
+            },
+            _                       => Outcome::Searching(*cursor),
+        }
+    }
+
+    fn jump(&mut self, buffer: &Buffer, from: &Cursor, skip_current: bool) -> Outcome {
+        if self.query.is_empty() {
+            self.found = true;
+            return Outcome::Searching(self.origin);
+        }
+        match find(buffer, &self.query, from, self.direction, skip_current) {
+            Some(found) => {
+                self.found = true;
+                Outcome::Searching(found)
+            },
+            None => {
+                self.found = false;
+                Outcome::Searching(*from)
+            },
+        }
+    }
+}
+
+// char columns where `query` shows up in `line`, as half open ranges
+pub fn find_in_line(line: &str, query: &str) -> Vec<(usize, usize)> {
+    if query.is_empty() {
+        return Vec::new();
+    }
+    let query_length = query.chars().count();
+    line.match_indices(query).map(|(byte, _)| {
+        let start = line[..byte].chars().count();
+        (start, start + query_length)
+    }).collect()
+}
+
+// Closest match of `query` going in `direction` from `from`. A match right at
+// `from` counts unless `skip_current` is set, which is how we move on to the
+// next one.
+pub fn find(buffer: &Buffer, query: &str, from: &Cursor, direction: Direction,
+            skip_current: bool) -> Option<Cursor> {
+    let count = buffer.count_lines();
+    if query.is_empty() || count == 0 {
+        return None;
+    }
+    let from_y = from.y.min(count - 1);
+
+    // the starting line is looked at twice: first the part ahead of the
+    // cursor, then after wrapping around, the part behind it
+    for step in 0..count + 1 {
+        let wrapped = step == count;
+        let y = match direction {
+            Direction::Forward  => (from_y + step) % count,
+            Direction::Backward => (from_y + count - step % count) % count,
+        };
+        let matches = find_in_line(&buffer.get_line(y), query);
+        let accepted = |start: usize| {
+            if y != from_y {
+                return true;
+            }
+            match (direction, wrapped) {
+                (Direction::Forward, false)  => start > from.x || (start == from.x && !skip_current),
+                (Direction::Forward, true)   => start < from.x || (start == from.x && skip_current),
+                (Direction::Backward, false) => start < from.x || (start == from.x && !skip_current),
+                (Direction::Backward, true)  => start > from.x || (start == from.x && skip_current),
+            }
+        };
+        let mut starts = matches.into_iter().map(|(start, _)| start).filter(|&start| accepted(start));
+        let found = match direction {
+            Direction::Forward  => starts.next(),
+            Direction::Backward => starts.next_back(),
+        };
+        if let Some(x) = found {
+            return Some(Cursor::new(x, y));
+        }
+    }
+    None
+}
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use backend::Key;
+    use {Buffer, Cursor};
+
+    #[test]
+    fn test_find_in_line() {
+        assert_eq!(find_in_line("let x = x + 1;", "x"), vec![(4, 5), (8, 9)]);
+        assert_eq!(find_in_line("añob año", "año"), vec![(0, 3), (5, 8)]);
+        assert_eq!(find_in_line("nothing here", "x"), vec![]);
+        assert_eq!(find_in_line("anything", ""), vec![]);
+    }
+
+    #[test]
+    fn test_find_forward_wraps_around() {
+        let buffer = Buffer::from_string("foo bar\nbaz\nfoo again");
+        let from = Cursor::new(1, 0);
+        assert_eq!(find(&buffer, "foo", &from, Direction::Forward, false), Some(Cursor::new(0, 2)));
+        let from = Cursor::new(5, 2);
+        assert_eq!(find(&buffer, "foo", &from, Direction::Forward, false), Some(Cursor::new(0, 0)));
+        assert_eq!(find(&buffer, "nope", &from, Direction::Forward, false), None);
+    }
+
+    #[test]
+    fn test_find_current_match() {
+        let buffer = Buffer::from_string("foo foo");
+        let from = Cursor::new(4, 0);
+        // typing more of the query keeps us on the same match
+        assert_eq!(find(&buffer, "foo", &from, Direction::Forward, false), Some(Cursor::new(4, 0)));
+        // asking for the next one moves on, wrapping if needed
+        assert_eq!(find(&buffer, "foo", &from, Direction::Forward, true), Some(Cursor::new(0, 0)));
+        assert_eq!(find(&buffer, "foo", &from, Direction::Backward, true), Some(Cursor::new(0, 0)));
+
+        // a single match is found again after going all the way around
+        let buffer = Buffer::from_string("only one");
+        let from = Cursor::new(5, 0);
+        assert_eq!(find(&buffer, "one", &from, Direction::Forward, true), Some(Cursor::new(5, 0)));
+        assert_eq!(find(&buffer, "one", &from, Direction::Backward, true), Some(Cursor::new(5, 0)));
+    }
+
+    fn cursor_after(outcome: Outcome) -> Cursor {
+        match outcome {
+            Outcome::Searching(cursor) => cursor,
+            _ => panic!("expected to be searching"),
+        }
+    }
+
+    #[test]
+    fn test_incremental_search_keys() {
+        let buffer = Buffer::from_string("fn main() {\n    let mask = 1;\n    main();\n}");
+        let origin = Cursor::new(0, 1);
+        let mut search = Search::new(origin, 0, Direction::Forward);
+
+        let cursor = cursor_after(search.handle_key(Key::Char('m'), &buffer, &origin));
+        assert_eq!(cursor, Cursor::new(8, 1));
+        let cursor = cursor_after(search.handle_key(Key::Char('a'), &buffer, &cursor));
+        assert_eq!(cursor, Cursor::new(8, 1));
+        let cursor = cursor_after(search.handle_key(Key::Char('i'), &buffer, &cursor));
+        assert_eq!(cursor, Cursor::new(4, 2));
+        assert_eq!(search.prompt(), "Search: mai");
+
+        // next one wraps around to the first line
+        let cursor = cursor_after(search.handle_key(Key::Ctrl('f'), &buffer, &cursor));
+        assert_eq!(cursor, Cursor::new(3, 0));
+        // and back
+        let cursor = cursor_after(search.handle_key(Key::Ctrl('r'), &buffer, &cursor));
+        assert_eq!(cursor, Cursor::new(4, 2));
+        assert_eq!(search.prompt(), "Search backward: mai");
+
+        // nothing matches, the cursor stays put
+        let cursor = cursor_after(search.handle_key(Key::Char('x'), &buffer, &cursor));
+        assert_eq!(cursor, Cursor::new(4, 2));
+        assert!(!search.found);
+        assert_eq!(search.prompt(), "Failing search backward: maix");
+
+        // deleting goes back to searching from the origin
+        let cursor = cursor_after(search.handle_key(Key::Backspace, &buffer, &cursor));
+        assert_eq!(cursor, Cursor::new(3, 0));
+        assert!(search.found);
+
+        match search.handle_key(Key::Esc, &buffer, &cursor) {
+            Outcome::Cancelled => {},
+            _ => panic!("escape should cancel"),
+        }
+        match search.handle_key(Key::Enter, &buffer, &cursor) {
+            Outcome::Accepted => {},
+            _ => panic!("enter should accept"),
+        }
+    }
+
+    #[test]
+    fn test_find_backward() {
+        let buffer = Buffer::from_string("a match\nnothing\nmatch match");
+        let from = Cursor::new(6, 2);
+        assert_eq!(find(&buffer, "match", &from, Direction::Backward, false), Some(Cursor::new(6, 2)));
+        assert_eq!(find(&buffer, "match", &from, Direction::Backward, true), Some(Cursor::new(0, 2)));
+        let from = Cursor::new(0, 2);
+        assert_eq!(find(&buffer, "match", &from, Direction::Backward, true), Some(Cursor::new(2, 0)));
+        let from = Cursor::new(0, 0);
+        assert_eq!(find(&buffer, "match", &from, Direction::Backward, true), Some(Cursor::new(6, 2)));
+    }
+}
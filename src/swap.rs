@@ -0,0 +1,158 @@
+// Crash recovery.
+//
+// While a buffer has unsaved changes its text is copied every couple of
+// seconds to a swap file next to the file being edited, .name.swp, so being
+// killed or panicking loses at most the last few keystrokes. The swap file is
+// removed once the buffer is saved, or when we quit without saving. One left
+// behind that's newer than the file means we didn't get that far, and it's
+// offered back when the file is opened again.
+
+use std::fs::{self, File, Permissions};
+use std::io;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use file;
+
+// how long after a change the swap file is written
+const DELAY: Duration = Duration::from_secs(2);
+
+pub struct Swap {
+    path: PathBuf,
+    // the buffer's revision that's in the swap file
+    written: usize,
+    // when the buffer first changed since then
+    changed: Option<Instant>,
+}
+
+impl Swap {
+    pub fn new(target: &Path) -> Swap {
+        let name = target.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+        Swap {
+            path: target.with_file_name(format!(".{}.swp", name)),
+            written: 0,
+            changed: None,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    // The text of a swap file left behind by an editor that didn't quit
+    // cleanly, if it's newer than the file.
+    pub fn recoverable(&self, target: &Path) -> Option<String> {
+        let swapped = fs::metadata(&self.path).and_then(|metadata| metadata.modified()).ok()?;
+        let saved = fs::metadata(target).and_then(|metadata| metadata.modified());
+        if saved.is_ok_and(|saved| saved >= swapped) {
+            return None;
+        }
+        fs::read_to_string(&self.path).ok()
+    }
+
+    // notes that the buffer is at `revision`, starting the clock if that's
+    // not what's in the swap file
+    pub fn update(&mut self, revision: usize) {
+        if revision != self.written && self.changed.is_none() {
+            self.changed = Some(Instant::now());
+        }
+    }
+
+    // how long we can wait for a key before the swap file is due
+    pub fn timeout(&self) -> Option<Duration> {
+        self.changed.map(|changed| DELAY.saturating_sub(changed.elapsed()))
+    }
+
+    pub fn is_due(&self) -> bool {
+        self.changed.is_some_and(|changed| changed.elapsed() >= DELAY)
+    }
+
+    // Goes to a temporary file renamed over the swap file, so being killed
+    // halfway leaves the last one whole. Only we can read it, it may be a
+    // copy of a file others can't.
+    pub fn write<F>(&mut self, revision: usize, write: F) -> io::Result<()>
+        where F: Fn(&mut File) -> io::Result<()>
+    {
+        self.changed = None;
+        let (temporary, mut file) = file::create_temporary(&self.path)?;
+        let written = fs::set_permissions(&temporary, Permissions::from_mode(0o600))
+            .and_then(|_| write(&mut file))
+            .and_then(|_| file.sync_data())
+            .and_then(|_| fs::rename(&temporary, &self.path));
+        if written.is_err() {
+            let _ = fs::remove_file(&temporary);
+        }
+        written?;
+        self.written = revision;
+        Ok(())
+    }
+
+    // the file has the buffer's text now, or it's being thrown away
+    pub fn remove(&mut self, revision: usize) {
+        self.changed = None;
+        self.written = revision;
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use std::env;
+    use std::io::Write;
+    use std::process;
+    use std::time::SystemTime;
+
+    #[test]
+    fn test_swap_file_round_trip() {
+        let dir = env::temp_dir().join(format!("editor2-swap-{}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let target = dir.join("file.txt");
+        let mut swap = Swap::new(&target);
+        assert_eq!(swap.path(), dir.join(".file.txt.swp"));
+        assert_eq!(swap.timeout(), None);
+
+        // a new file that was never saved
+        swap.update(1);
+        assert!(swap.timeout().is_some() && !swap.is_due());
+        swap.write(1, |file| file.write_all(b"unsaved")).unwrap();
+        assert_eq!(swap.timeout(), None);
+        assert_eq!(swap.recoverable(&target).unwrap(), "unsaved");
+        swap.update(1);
+        assert_eq!(swap.timeout(), None);
+
+        // saved after the swap file was written
+        fs::write(&target, "saved").unwrap();
+        File::open(&target).unwrap().set_modified(SystemTime::now() + Duration::from_secs(10)).unwrap();
+        assert_eq!(swap.recoverable(&target), None);
+
+        swap.remove(1);
+        assert!(!swap.path().exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_failed_write_keeps_the_last_swap_file() {
+        let dir = env::temp_dir().join(format!("editor2-swap-failed-{}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let target = dir.join("file.txt");
+        let mut swap = Swap::new(&target);
+        swap.write(1, |file| file.write_all(b"first")).unwrap();
+        assert_eq!(fs::metadata(swap.path()).unwrap().permissions().mode() & 0o777, 0o600);
+
+        let failed = swap.write(2, |file| {
+            file.write_all(b"sec")?;
+            Err(io::Error::other("disk full"))
+        });
+        assert!(failed.is_err());
+        assert_eq!(fs::read_to_string(swap.path()).unwrap(), "first");
+        // and nothing else is left behind
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,497 @@
+//! Colour themes.
+//!
+//! Everything on screen is drawn with the Face of some Class: a token the
+//! highlighter found, or a part of the editor like the gutter or the status
+//! bar. A theme is a TOML file giving classes a face in a few words, like
+//!
+//! ```toml
+//! keyword = "bold #fb4934"
+//! match = "black on yellow | reverse"
+//! ```
+//!
+//! that is a colour, maybe "on" a background colour, and any of bold,
+//! underline and reverse. Colours are default, one of the eight basic names,
+//! a number from the 256-colour palette or #rrggbb. What comes after a `|` is
+//! used on terminals with only eight colours, without one the closest basic
+//! colours are picked. A default colour is the one the text has. Classes a
+//! theme leaves out look like in its `base`, one of the built-in themes, or
+//! like plain text if it has none.
+//!
+//! Terminals that say they do 256 colours get the palette, with #rrggbb shown
+//! as the closest one in it. Ones that say they do truecolor get #rrggbb as
+//! it is.
+
+use std::env;
+use std::fs;
+
+use backend::{Color, Style};
+use config::{self, Value};
+use highlight::Token;
+
+const BUILT_IN: [(&str, &str); 2] = [
+    ("dark", include_str!("../themes/dark.toml")),
+    ("light", include_str!("../themes/light.toml")),
+];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Class {
+    Text,
+    Keyword,
+    Type,
+    Number,
+    String,
+    Char,
+    Lifetime,
+    Comment,
+    Attribute,
+    Macro,
+    Symbol,
+    Selection,
+    // search matches
+    Match,
+    Gutter,
+    // the markers on lines that go on past the screen
+    Continuation,
+    Status,
+    Prompt,
+}
+
+// in the same order as the enum
+const CLASSES: [&str; 17] = [
+    "text", "keyword", "type", "number", "string", "char", "lifetime", "comment", "attribute", "macro",
+    "symbol", "selection", "match", "gutter", "continuation", "status", "prompt",
+];
+
+impl Class {
+    fn from_token(token: Token) -> Class {
+        match token {
+            Token::Ident     => Class::Text,
+            Token::Keyword   => Class::Keyword,
+            Token::Type      => Class::Type,
+            Token::Number    => Class::Number,
+            Token::String    => Class::String,
+            Token::Char      => Class::Char,
+            Token::Lifetime  => Class::Lifetime,
+            Token::Comment   => Class::Comment,
+            Token::Attribute => Class::Attribute,
+            Token::Macro     => Class::Macro,
+            Token::Symbol    => Class::Symbol,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Palette {
+    // the eight basic colours
+    Basic,
+    // all 256
+    Extended,
+    // all 256, and #rrggbb as it is
+    TrueColor,
+}
+
+impl Palette {
+    /// EDITOR2_COLORS=8|256|truecolor overrides what the terminal says
+    pub fn from_env() -> Palette {
+        palette(env::var("EDITOR2_COLORS").ok(), env::var("COLORTERM").ok(), env::var("TERM").ok())
+    }
+}
+
+fn palette(colors: Option<String>, colorterm: Option<String>, term: Option<String>) -> Palette {
+    match colors.as_ref().map(|colors| &colors[..]) {
+        Some("8") => return Palette::Basic,
+        Some("256") => return Palette::Extended,
+        Some("truecolor") => return Palette::TrueColor,
+        _ => {},
+    }
+    if colorterm.is_some_and(|colorterm| colorterm == "truecolor" || colorterm == "24bit") {
+        Palette::TrueColor
+    } else if term.is_some_and(|term| term.contains("256color")) {
+        Palette::Extended
+    } else {
+        Palette::Basic
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Colour {
+    Default,
+    // black, red, green, yellow, blue, magenta, cyan and white
+    Basic(u8),
+    Byte(u8),
+    Rgb(u8, u8, u8),
+}
+
+const BASIC_NAMES: [&str; 8] = ["black", "red", "green", "yellow", "blue", "magenta", "cyan", "white"];
+
+// what xterm shows the first 16 colours of the palette as
+const SYSTEM_COLOURS: [(u8, u8, u8); 16] = [
+    (0, 0, 0), (205, 0, 0), (0, 205, 0), (205, 205, 0), (0, 0, 238), (205, 0, 205), (0, 205, 205), (229, 229, 229),
+    (127, 127, 127), (255, 0, 0), (0, 255, 0), (255, 255, 0), (92, 92, 255), (255, 0, 255), (0, 255, 255),
+    (255, 255, 255),
+];
+
+// the levels of each channel in the 6x6x6 colour cube
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+impl Colour {
+    fn parse(word: &str) -> Option<Colour> {
+        if word == "default" {
+            return Some(Colour::Default);
+        }
+        if let Some(index) = BASIC_NAMES.iter().position(|&name| name == word) {
+            return Some(Colour::Basic(index as u8));
+        }
+        if let Some(hex) = word.strip_prefix('#') {
+            let channel = |i: usize| hex.get(i..i + 2).and_then(|digits| u8::from_str_radix(digits, 16).ok());
+            return match (hex.len(), channel(0), channel(2), channel(4)) {
+                (6, Some(red), Some(green), Some(blue)) => Some(Colour::Rgb(red, green, blue)),
+                _ => None,
+            };
+        }
+        word.parse().ok().map(Colour::Byte)
+    }
+
+    // whether eight colours aren't enough for it
+    fn is_extended(self) -> bool {
+        matches!(self, Colour::Byte(_) | Colour::Rgb(..))
+    }
+
+    fn is_rgb(self) -> bool {
+        matches!(self, Colour::Rgb(..))
+    }
+
+    // `text` is what default stands for, #rrggbb is kept if `rgb`
+    fn to_extended(self, text: u8, rgb: bool) -> Color {
+        match self {
+            Colour::Rgb(red, green, blue) if rgb => Color::Rgb(red, green, blue),
+            colour => colour.to_256(text),
+        }
+    }
+
+    fn to_256(self, text: u8) -> Color {
+        Color::Byte(match self {
+            Colour::Default => text,
+            Colour::Basic(index) => index,
+            Colour::Byte(index) => index,
+            Colour::Rgb(red, green, blue) => closest_in_palette(red, green, blue),
+        })
+    }
+
+    fn to_basic(self) -> Color {
+        let index = match self {
+            Colour::Default => return Color::Default,
+            Colour::Basic(index) => index,
+            Colour::Byte(index) if index < 16 => index % 8,
+            Colour::Byte(index) => {
+                let (red, green, blue) = palette_rgb(index);
+                closest_basic(red, green, blue)
+            },
+            Colour::Rgb(red, green, blue) => closest_basic(red, green, blue),
+        };
+        [Color::Black, Color::Red, Color::Green, Color::Yellow,
+         Color::Blue, Color::Magenta, Color::Cyan, Color::White][index as usize]
+    }
+}
+
+fn distance(a: (u8, u8, u8), b: (u8, u8, u8)) -> u32 {
+    let square = |x: u8, y: u8| (x as i32 - y as i32).pow(2) as u32;
+    square(a.0, b.0) + square(a.1, b.1) + square(a.2, b.2)
+}
+
+fn palette_rgb(index: u8) -> (u8, u8, u8) {
+    match index {
+        0..=15 => SYSTEM_COLOURS[index as usize],
+        16..=231 => {
+            let cube = index - 16;
+            (CUBE_LEVELS[(cube / 36) as usize], CUBE_LEVELS[(cube / 6 % 6) as usize], CUBE_LEVELS[(cube % 6) as usize])
+        },
+        _ => {
+            let level = 8 + 10 * (index - 232);
+            (level, level, level)
+        },
+    }
+}
+
+// The closest colour in the cube or the greys. The first 16 are left out,
+// terminals are free to show them however they like.
+pub(crate) fn closest_in_palette(red: u8, green: u8, blue: u8) -> u8 {
+    let level = |channel: u8| {
+        (0..6).min_by_key(|&i| (CUBE_LEVELS[i] as i32 - channel as i32).abs()).unwrap() as u8
+    };
+    let cube = 16 + 36 * level(red) + 6 * level(green) + level(blue);
+    let average = (red as u32 + green as u32 + blue as u32) / 3;
+    let grey = 232 + ((average.saturating_sub(3) / 10).min(23)) as u8;
+    let rgb = (red, green, blue);
+    if distance(palette_rgb(grey), rgb) < distance(palette_rgb(cube), rgb) { grey } else { cube }
+}
+
+fn closest_basic(red: u8, green: u8, blue: u8) -> u8 {
+    (0..8).min_by_key(|&i| distance(SYSTEM_COLOURS[i as usize], (red, green, blue))).unwrap()
+}
+
+// a face as the theme has it, before knowing what the terminal can show
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Spec {
+    fg: Colour,
+    bg: Colour,
+    style: Style,
+}
+
+impl Spec {
+    fn plain() -> Spec {
+        Spec {fg: Colour::Default, bg: Colour::Default, style: Style::NORMAL}
+    }
+
+    fn parse(text: &str) -> Result<Spec, String> {
+        let mut spec = Spec::plain();
+        let mut background = false;
+        for word in text.split_whitespace() {
+            match word {
+                "bold" => spec.style = spec.style | Style::BOLD,
+                "underline" => spec.style = spec.style | Style::UNDERLINE,
+                "reverse" => spec.style = spec.style | Style::REVERSE,
+                "on" => background = true,
+                _ => {
+                    let colour = Colour::parse(word).ok_or_else(|| format!("unknown colour or style {}", word))?;
+                    if background {
+                        spec.bg = colour;
+                    } else {
+                        spec.fg = colour;
+                    }
+                },
+            }
+        }
+        Ok(spec)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Face {
+    pub style: Style,
+    pub fg: Color,
+    pub bg: Color,
+}
+
+#[derive(Clone)]
+pub struct Theme {
+    /// whether it needs termbox's 256 colour mode
+    pub extended: bool,
+    /// whether it has 24-bit colours, which termbox can't send
+    pub truecolor: bool,
+    // one for each class
+    faces: Vec<Face>,
+}
+
+impl Theme {
+    pub fn face(&self, class: Class) -> Face {
+        self.faces[class as usize]
+    }
+
+    pub fn token(&self, token: Token) -> Face {
+        self.face(Class::from_token(token))
+    }
+
+    pub fn built_in(name: &str, palette: Palette) -> Option<Theme> {
+        let definition = BUILT_IN.iter().find(|&&(built_in, _)| built_in == name)?.1;
+        Some(Theme::from_definition(definition, palette).expect("built-in themes are valid"))
+    }
+
+    /// A path, the name of a file in the user's themes directory or the name
+    /// of a built-in theme.
+    pub fn load(name: &str, palette: Palette) -> Result<Theme, String> {
+        let path = if name.contains('/') || name.ends_with(".toml") {
+            Some(name.into())
+        } else {
+            config::config_dir()
+                .map(|dir| dir.join("themes").join(format!("{}.toml", name)))
+                .filter(|path| path.exists())
+        };
+        match path {
+            Some(path) => {
+                let definition = fs::read_to_string(&path).map_err(|error| error.to_string())?;
+                Theme::from_definition(&definition, palette)
+            },
+            None => Theme::built_in(name, palette).ok_or_else(|| "no such theme".to_string()),
+        }
+    }
+
+    pub fn from_definition(definition: &str, palette: Palette) -> Result<Theme, String> {
+        Ok(Theme::resolve(&parse(definition)?, palette))
+    }
+
+    // Works out the colours the terminal will show. Termbox's 256 colour mode
+    // has no default colour, so that's the text's or white on black.
+    fn resolve(specs: &[(Spec, Spec)], palette: Palette) -> Theme {
+        let extended = palette != Palette::Basic
+            && specs.iter().any(|&(spec, _)| spec.fg.is_extended() || spec.bg.is_extended());
+        let rgb = palette == Palette::TrueColor;
+        let (text, basic_text) = specs[Class::Text as usize];
+        let (text_fg, text_bg) = (text.fg.to_extended(7, rgb), text.bg.to_extended(0, rgb));
+        let (basic_text_fg, basic_text_bg) = (basic_text.fg.to_basic(), basic_text.bg.to_basic());
+
+        let faces = specs.iter().map(|&(spec, basic)| {
+            if extended {
+                let fg = match spec.fg { Colour::Default => text_fg, fg => fg.to_extended(7, rgb) };
+                let bg = match spec.bg { Colour::Default => text_bg, bg => bg.to_extended(0, rgb) };
+                Face {style: spec.style, fg, bg}
+            } else {
+                let fg = match basic.fg { Colour::Default => basic_text_fg, fg => fg.to_basic() };
+                let bg = match basic.bg { Colour::Default => basic_text_bg, bg => bg.to_basic() };
+                Face {style: basic.style, fg, bg}
+            }
+        }).collect();
+        let truecolor = extended && rgb && specs.iter().any(|&(spec, _)| spec.fg.is_rgb() || spec.bg.is_rgb());
+        Theme {extended, truecolor, faces}
+    }
+}
+
+// The faces of every class, for 256 and for eight colours. The name is just
+// for whoever reads the file.
+fn parse(definition: &str) -> Result<Vec<(Spec, Spec)>, String> {
+    let pairs = config::parse(definition)?;
+    let mut specs = vec![(Spec::plain(), Spec::plain()); CLASSES.len()];
+
+    // the base comes first whatever line it's on
+    for &(line, ref key, ref value) in &pairs {
+        if let ("base", Value::Text(base)) = (&key[..], value) {
+            let definition = BUILT_IN.iter().find(|&&(built_in, _)| built_in == base)
+                .ok_or_else(|| format!("line {}: no built-in theme {}", line, base))?.1;
+            specs = parse(definition)?;
+        }
+    }
+
+    for (line, key, value) in pairs {
+        let error = |message: String| format!("line {}: {}", line, message);
+        match (&key[..], value) {
+            ("name", Value::Text(_)) | ("base", Value::Text(_)) => {},
+            (key, Value::Text(text)) if CLASSES.contains(&key) => {
+                let index = CLASSES.iter().position(|&class| class == key).unwrap();
+                let mut parts = text.splitn(2, '|');
+                let spec = Spec::parse(parts.next().unwrap()).map_err(&error)?;
+                let basic = match parts.next() {
+                    Some(basic) => Spec::parse(basic).map_err(&error)?,
+                    None => spec,
+                };
+                specs[index] = (spec, basic);
+            },
+            (key, _) => return Err(error(format!("unknown key or wrong type for {}", key))),
+        }
+    }
+    Ok(specs)
+}
+
+/// The theme EDITOR2_THEME names, dark by default. When it can't be loaded
+/// it's the dark one and why.
+pub fn from_env(palette: Palette) -> (Theme, Option<String>) {
+    let name = env::var("EDITOR2_THEME").unwrap_or_else(|_| "dark".to_string());
+    match Theme::load(&name, palette) {
+        Ok(theme) => (theme, None),
+        Err(error) => {
+            let dark = Theme::built_in("dark", palette).unwrap();
+            (dark, Some(format!("Couldn't load theme {}: {}", name, error)))
+        },
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_parse_faces() {
+        assert_eq!(Spec::parse("bold red on #102030"), Ok(Spec {
+            fg: Colour::Basic(1), bg: Colour::Rgb(16, 32, 48), style: Style::BOLD,
+        }));
+        assert_eq!(Spec::parse("on 236 underline reverse"), Ok(Spec {
+            fg: Colour::Default, bg: Colour::Byte(236), style: Style::UNDERLINE | Style::REVERSE,
+        }));
+        assert_eq!(Spec::parse("italic"), Err("unknown colour or style italic".to_string()));
+        assert_eq!(Spec::parse("#12345"), Err("unknown colour or style #12345".to_string()));
+    }
+
+    #[test]
+    fn test_closest_colours() {
+        assert_eq!(closest_in_palette(255, 0, 0), 196);
+        assert_eq!(closest_in_palette(0xfb, 0x49, 0x34), 203);
+        assert_eq!(closest_in_palette(128, 128, 128), 244);
+        assert_eq!(closest_in_palette(0, 0, 0), 16);
+        assert_eq!(closest_basic(0xfb, 0x49, 0x34), 1);
+        assert_eq!(closest_basic(30, 30, 30), 0);
+        assert_eq!(Colour::Byte(203).to_basic(), Color::Red);
+        assert_eq!(Colour::Byte(9).to_basic(), Color::Red);
+    }
+
+    #[test]
+    fn test_themes_follow_the_palette() {
+        let dark = Theme::built_in("dark", Palette::Extended).unwrap();
+        assert!(dark.extended);
+        assert_eq!(dark.token(Token::Keyword),
+                   Face {style: Style::NORMAL, fg: Color::Byte(203), bg: Color::Byte(235)});
+        // the default background is the text's
+        assert_eq!(dark.face(Class::Prompt).bg, Color::Byte(235));
+
+        // eight colours use what comes after the |
+        let dark = Theme::built_in("dark", Palette::Basic).unwrap();
+        assert!(!dark.extended);
+        assert_eq!(dark.face(Class::Text), Face {style: Style::NORMAL, fg: Color::Default, bg: Color::Black});
+        assert_eq!(dark.face(Class::Selection).style, Style::REVERSE);
+        assert_eq!(dark.token(Token::Lifetime), Face {style: Style::BOLD, fg: Color::Yellow, bg: Color::Black});
+
+        let light = Theme::built_in("light", Palette::Basic).unwrap();
+        assert_eq!(light.face(Class::Status), Face {style: Style::NORMAL, fg: Color::White, bg: Color::Blue});
+        assert!(Theme::built_in("solarized", Palette::Basic).is_none());
+    }
+
+    #[test]
+    fn test_truecolor_themes_keep_rgb() {
+        let dark = Theme::built_in("dark", Palette::TrueColor).unwrap();
+        assert!(dark.extended && dark.truecolor);
+        assert_eq!(dark.token(Token::Keyword),
+                   Face {style: Style::NORMAL, fg: Color::Rgb(0xfb, 0x49, 0x34), bg: Color::Rgb(0x28, 0x28, 0x28)});
+
+        // nothing to send in 24 bits
+        let theme = Theme::from_definition("name = \"mine\"\nkeyword = \"bold 196\"\n", Palette::TrueColor).unwrap();
+        assert!(theme.extended && !theme.truecolor);
+        assert_eq!(theme.token(Token::Keyword).fg, Color::Byte(196));
+    }
+
+    #[test]
+    fn test_user_themes() {
+        let theme = Theme::from_definition("name = \"mine\"\nkeyword = \"bold 196\"\n", Palette::Extended).unwrap();
+        assert_eq!(theme.token(Token::Keyword),
+                   Face {style: Style::BOLD, fg: Color::Byte(196), bg: Color::Byte(0)});
+        assert_eq!(theme.token(Token::Comment),
+                   Face {style: Style::NORMAL, fg: Color::Byte(7), bg: Color::Byte(0)});
+
+        // with only basic colours termbox's default ones are kept
+        let theme = Theme::from_definition("name = \"mine\"\nkeyword = \"red\"\n", Palette::Extended).unwrap();
+        assert!(!theme.extended);
+        assert_eq!(theme.token(Token::Ident).fg, Color::Default);
+
+        // what's missing comes from the base
+        let definition = "name = \"x\"\nstring = \"blue\"\nbase = \"light\"";
+        let theme = Theme::from_definition(definition, Palette::Basic).unwrap();
+        assert_eq!(theme.token(Token::String).fg, Color::Blue);
+        assert_eq!(theme.token(Token::Keyword).fg, Color::Magenta);
+
+        let error = |definition: &str| Theme::from_definition(definition, Palette::Basic).err().unwrap();
+        assert_eq!(error("name = \"x\"\nkeywords = \"red\""), "line 2: unknown key or wrong type for keywords");
+        assert_eq!(error("name = \"x\"\nkeyword = \"red | blink\""), "line 2: unknown colour or style blink");
+        assert_eq!(error("base = \"x\""), "line 1: no built-in theme x");
+    }
+
+    #[test]
+    fn test_palette_detection() {
+        let some = |value: &str| Some(value.to_string());
+        assert_eq!(palette(None, None, some("xterm")), Palette::Basic);
+        assert_eq!(palette(None, None, some("xterm-256color")), Palette::Extended);
+        assert_eq!(palette(None, some("truecolor"), some("xterm")), Palette::TrueColor);
+        assert_eq!(palette(None, some("24bit"), some("xterm-256color")), Palette::TrueColor);
+        assert_eq!(palette(some("8"), some("truecolor"), some("xterm-256color")), Palette::Basic);
+        assert_eq!(palette(some("256"), some("truecolor"), None), Palette::Extended);
+        assert_eq!(palette(some("truecolor"), None, some("xterm")), Palette::TrueColor);
+    }
+}
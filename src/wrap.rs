@@ -0,0 +1,115 @@
+// Soft wrapping.
+//
+// A line too long for the screen is shown on several rows. Rows break after
+// the last space that fits, or anywhere when a word is longer than a row. A
+// line is described by the columns its rows start at, the first one is
+// always 0. The last row always has room for the cursor after the last char,
+// so a line that exactly fills its rows gets an empty one at the end.
+
+use {Buffer, Cursor};
+
+pub fn wrap_line(line: &str, width: usize) -> Vec<usize> {
+    let characters: Vec<char> = line.chars().collect();
+    let width = width.max(1);
+    let mut starts = vec![0];
+    let mut start = 0;
+    while characters.len() - start >= width {
+        let end = start + width;
+        let row_end = (start + 1..end + 1).rev()
+            .find(|&x| characters[x - 1] == ' ')
+            .unwrap_or(end);
+        starts.push(row_end);
+        start = row_end;
+    }
+    starts
+}
+
+// row of the line a column is on, and where in that row
+pub fn visual_position(starts: &[usize], x: usize) -> (usize, usize) {
+    let row = starts.iter().rposition(|&start| start <= x).unwrap_or(0);
+    (row, x - starts[row])
+}
+
+// the last column the cursor can be at on a row
+fn row_end(starts: &[usize], row: usize, line_length: usize) -> usize {
+    match starts.get(row + 1) {
+        Some(&next) => next - 1,
+        None => line_length,
+    }
+}
+
+// Moves the cursor a row up or down, into the next or previous line when it's
+// on the first or last row of its own, keeping the column within the row when
+// it can.
+pub fn next_visual_cursor(cursor: &Cursor, buffer: &Buffer, up: bool, width: usize) -> Cursor {
+    let &Cursor{x, y} = cursor;
+    let line_length = buffer.get_line_length(y);
+    let starts = wrap_line(&buffer.get_line(y), width);
+    let (row, column) = visual_position(&starts, x.min(line_length));
+
+    let (y, starts, row, line_length) = if up && row > 0 {
+        (y, starts, row - 1, line_length)
+    } else if !up && row + 1 < starts.len() {
+        (y, starts, row + 1, line_length)
+    } else if up && y > 0 {
+        let starts = wrap_line(&buffer.get_line(y - 1), width);
+        let row = starts.len() - 1;
+        (y - 1, starts, row, buffer.get_line_length(y - 1))
+    } else if !up && y + 1 < buffer.count_lines() {
+        let starts = wrap_line(&buffer.get_line(y + 1), width);
+        (y + 1, starts, 0, buffer.get_line_length(y + 1))
+    } else {
+        return *cursor;
+    };
+    let x = (starts[row] + column).min(row_end(&starts, row, line_length));
+    Cursor::new(x, y)
+}
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use {Buffer, Cursor};
+
+    #[test]
+    fn test_wrap_at_word_boundaries() {
+        assert_eq!(wrap_line("", 10), vec![0]);
+        assert_eq!(wrap_line("short", 10), vec![0]);
+        assert_eq!(wrap_line("the quick brown fox", 10), vec![0, 10]);
+        assert_eq!(wrap_line("the quick brown fox jumps", 8), vec![0, 4, 10, 16, 20]);
+        // no space to break at
+        assert_eq!(wrap_line("abcdefghij", 4), vec![0, 4, 8]);
+        // exactly full, the cursor needs a row after it
+        assert_eq!(wrap_line("abcd", 4), vec![0, 4]);
+    }
+
+    #[test]
+    fn test_visual_position() {
+        let starts = wrap_line("the quick brown fox", 10);
+        assert_eq!(visual_position(&starts, 0), (0, 0));
+        assert_eq!(visual_position(&starts, 9), (0, 9));
+        assert_eq!(visual_position(&starts, 10), (1, 0));
+        assert_eq!(visual_position(&starts, 19), (1, 9));
+    }
+
+    #[test]
+    fn test_move_by_visual_line() {
+        let buffer = Buffer::from_string("the quick brown fox\nab\nlonger line here");
+        let down = |cursor: Cursor| next_visual_cursor(&cursor, &buffer, false, 10);
+        let up = |cursor: Cursor| next_visual_cursor(&cursor, &buffer, true, 10);
+
+        // within the first line
+        assert_eq!(down(Cursor::new(2, 0)), Cursor::new(12, 0));
+        assert_eq!(up(Cursor::new(12, 0)), Cursor::new(2, 0));
+        // into the next line, clamped to its length
+        assert_eq!(down(Cursor::new(15, 0)), Cursor::new(2, 1));
+        // up into the last row of a wrapped line
+        assert_eq!(up(Cursor::new(1, 1)), Cursor::new(11, 0));
+        assert_eq!(down(Cursor::new(1, 1)), Cursor::new(1, 2));
+        // a shorter row keeps the cursor on it
+        assert_eq!(up(Cursor::new(5, 2)), Cursor::new(2, 1));
+        assert_eq!(up(Cursor::new(9, 0)), Cursor::new(9, 0));
+        assert_eq!(down(Cursor::new(14, 2)), Cursor::new(14, 2));
+    }
+}